@@ -1,83 +1,323 @@
+#[cfg(debug_assertions)]
+use std::{collections::HashMap, time::SystemTime};
 use std::{
     fs::{File, create_dir, exists},
     io::Read,
-    panic::catch_unwind,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
-use futures::executor::block_on;
 use macroquad::prelude::*;
-use once_cell::sync::Lazy;
+use serde::Deserialize;
 
-use crate::errors::{GameError, Nresult, Result};
-
-pub static MAPS: Lazy<Vec<Texture2D>> = Lazy::new(|| block_on(init_map()));
-pub static SPRITES: Lazy<Vec<Texture2D>> = Lazy::new(|| block_on(init_sprites()));
-pub static ENEMIES: Lazy<Vec<Texture2D>> = Lazy::new(|| block_on(init_enemies()));
+use crate::{
+    errors::{GameError, Result},
+    model::{
+        animation::{AnimEvent, AnimState, Clip, Clips},
+        enemies::enemymap::{ENEMY_DATA_LOC, EnemyMap},
+        weapons::weaponmap::{WEAPON_DATA_LOC, WeaponMap},
+    },
+};
 
-const ASSET_LOC: &str = "assets/";
+pub const ASSET_LOC: &str = "assets/";
 const MAP_LOC: &str = "maps/";
+const CROSSHAIR_LOC: &str = "crosshair.png";
 const PLAYER_ANIM_LOC: &str = "player_anim";
-const ENEMY_LOC: &str = "enemies";
+pub const ENEMY_LOC: &str = "enemies";
+const MANIFEST_LOC: &str = "manifest.json";
+
+/// Side length of the checkerboard drawn in place of a texture that failed to load.
+pub const PLACEHOLDER_SIZE: u16 = 16;
+/// Size of the map used in place of one that failed to load. Big enough that enemies can
+/// still spawn their usual 1000 units away from a player in the middle.
+const PLACEHOLDER_MAP_SIZE: u16 = 2048;
+
+/// Every asset the game needs, relative to `ASSET_LOC`. Read from `assets/manifest.json`.
+#[derive(Deserialize)]
+pub struct Manifest {
+    pub textures: Vec<PathBuf>,
+    /// JSON data files, such as enemy and weapon stats.
+    #[serde(default)]
+    pub data: Vec<PathBuf>,
+}
 
-pub fn init_all() -> Nresult {
-    if let Err(e) = catch_unwind(init_all_inner) {
-        error!("FAILED TO INIT!!! {:?}", e);
+impl Manifest {
+    pub fn load() -> Result<Self> {
+        let path = Path::new(ASSET_LOC).join(MANIFEST_LOC);
+        let file = File::open(&path).map_err(|_| GameError::NotFoundError(path.clone()))?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::AssetLoadFailure(format!("{}: {e}", path.display())))
+    }
+
+    /// Checks every listed data file parses and every texture has a readable image header.
+    /// Textures are only decoded once, by `Assets::load`. Returns every problem found, not just
+    /// the first.
+    pub fn validate(&self) -> Vec<GameError> {
+        let data = self.data.iter().filter_map(|rel| {
+            let path = Path::new(ASSET_LOC).join(rel);
+            let Ok(file) = File::open(&path) else {
+                return Some(GameError::NotFoundError(path));
+            };
+            serde_json::from_reader::<_, serde_json::Value>(file)
+                .err()
+                .map(|e| GameError::AssetLoadFailure(format!("{}: {e}", path.display())))
+        });
+        self.textures
+            .iter()
+            .filter_map(|rel| {
+                let path = Path::new(ASSET_LOC).join(rel);
+                let Ok(reader) = image::io::Reader::open(&path) else {
+                    return Some(GameError::NotFoundError(path));
+                };
+                let problem = match reader
+                    .with_guessed_format()
+                    .map_err(image::ImageError::from)
+                    .and_then(|el| el.into_dimensions())
+                {
+                    Ok((w, h)) if w > 0 && h > 0 => return None,
+                    Ok(_) => "empty image".to_owned(),
+                    Err(e) => e.to_string(),
+                };
+                Some(GameError::AssetLoadFailure(format!(
+                    "{}: {problem}",
+                    path.display()
+                )))
+            })
+            .chain(data)
+            .collect()
     }
-    Ok(())
 }
 
-fn init_all_inner() {
-    if !exists("save/").unwrap() {
-        let _ = create_dir("save/");
+/// Validates the asset manifest and loads everything. Missing or broken textures are replaced
+/// by placeholders, so the game still runs; every problem found is returned for reporting.
+pub async fn init_all() -> (Assets, Vec<GameError>) {
+    let mut problems = match Manifest::load() {
+        Ok(manifest) => manifest.validate(),
+        Err(e) => vec![e],
+    };
+    if !exists("save/").unwrap_or(false)
+        && let Err(e) = create_dir("save/")
+    {
+        problems.push(e.into());
     }
-    MAPS.is_empty();
-    SPRITES.is_empty();
+    (Assets::load().await, problems)
 }
 
-async fn init_map() -> Vec<Texture2D> {
-    let mut map_loc = PathBuf::from(ASSET_LOC);
-    map_loc.push(MAP_LOC);
-    let mut handles = Vec::new();
-    let mut maps = map_loc
-        .read_dir()
-        .unwrap()
-        .map(core::result::Result::unwrap)
-        .map(|el| el.path())
-        .collect::<Vec<_>>();
-    maps.sort_unstable_by(|a, b| a.file_name().unwrap().cmp(b.file_name().unwrap()));
+/// A texture and its size. The size is always there, so the model can work without a GPU.
+#[derive(Clone)]
+pub struct Sprite {
+    pub size: Vec2,
+    pub tex: Option<Texture2D>,
+}
 
-    for i in maps {
-        handles.push(gen_loader(i));
+impl Sprite {
+    pub fn new(tex: Texture2D) -> Self {
+        Sprite {
+            size: tex.size(),
+            tex: Some(tex),
+        }
+    }
+    /// Size only, nothing to draw.
+    #[cfg(test)]
+    pub fn stub(size: Vec2) -> Self {
+        Sprite { size, tex: None }
     }
-    futures::future::join_all(handles).await
 }
 
-async fn init_sprites() -> Vec<Texture2D> {
-    let mut player_anim_loc = PathBuf::from(ASSET_LOC);
-    player_anim_loc.push(PLAYER_ANIM_LOC);
-    let mut handles = Vec::new();
-    let mut files = player_anim_loc
-        .read_dir()
-        .unwrap()
-        .map(core::result::Result::unwrap)
-        .map(|el| el.path())
-        .collect::<Vec<_>>();
-    files.sort_unstable_by(|a, b| a.file_name().unwrap().cmp(b.file_name().unwrap()));
-    for i in files {
-        handles.push(gen_loader(i));
+/// Everything loaded from `ASSET_LOC`. Owned by `GameModel` and lent to whoever needs it.
+pub struct Assets {
+    pub maps: Vec<Sprite>,
+    pub player: Clips<Sprite>,
+    pub enemies: EnemyMap,
+    pub weapons: WeaponMap,
+    pub crosshair: Sprite,
+}
+
+impl Assets {
+    pub async fn load() -> Self {
+        Assets {
+            maps: load_maps().await,
+            player: load_player_clips().await,
+            enemies: EnemyMap::load().await,
+            weapons: WeaponMap::load(),
+            crosshair: load_crosshair().await,
+        }
+    }
+
+    /// Assets with correct sizes but no textures, for running the model without a GL context.
+    /// Nothing is read from disk; there is one enemy kind and the default weapon.
+    #[cfg(test)]
+    pub fn stub() -> Self {
+        let frame = Vec2::splat(PLACEHOLDER_SIZE as f32);
+        let mut player = Clips::new();
+        player.insert(
+            AnimState::Idle,
+            Clip::new(
+                vec![(PLAYER_FRAME_TIME, Sprite::stub(frame))],
+                AnimState::Idle.default_mode(),
+            ),
+        );
+        Assets {
+            maps: vec![Sprite::stub(Vec2::splat(PLACEHOLDER_MAP_SIZE as f32))],
+            player,
+            enemies: EnemyMap::stub(Rect::new(0., 0., frame.x, frame.y)),
+            weapons: WeaponMap::stub(),
+            crosshair: Sprite::stub(frame),
+        }
+    }
+
+    pub fn map(&self, id: u32) -> Result<&Sprite> {
+        self.maps
+            .get(id as usize)
+            .ok_or(GameError::AssetLoadFailure(format!("MAP {id} NOT FOUND")))
+    }
+
+    /// Reloads whatever group of assets `path` belongs to. Returns an error for files that
+    /// aren't loaded from `ASSET_LOC` by this type.
+    pub async fn reload(&mut self, path: &Path) -> Result<()> {
+        let rel = path.strip_prefix(ASSET_LOC).unwrap_or(path);
+        if rel.starts_with(MAP_LOC) {
+            self.maps = load_maps().await;
+        } else if rel.starts_with(PLAYER_ANIM_LOC) {
+            self.player = load_player_clips().await;
+        } else if rel.starts_with(ENEMY_LOC) || rel == Path::new(ENEMY_DATA_LOC) {
+            self.enemies = EnemyMap::load().await;
+        } else if rel == Path::new(WEAPON_DATA_LOC) {
+            self.weapons = WeaponMap::load();
+        } else if rel == Path::new(CROSSHAIR_LOC) {
+            self.crosshair = load_crosshair().await;
+        } else {
+            return Err(GameError::UnsupportedOperation(format!(
+                "don't know how to reload {}",
+                path.display()
+            )));
+        }
+        Ok(())
     }
-    futures::future::join_all(handles).await
 }
 
-async fn init_enemies() -> Vec<Texture2D> {
+/// Lists the files in `dir`, sorted by name. A missing directory is logged and treated as empty.
+pub fn read_dir_sorted(dir: &Path) -> Vec<PathBuf> {
+    let mut files = match dir.read_dir() {
+        Ok(entries) => entries
+            .filter_map(core::result::Result::ok)
+            .map(|el| el.path())
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            error!("Could not read {}: {e}", dir.display());
+            Vec::new()
+        }
+    };
+    files.sort_unstable();
+    files
+}
+
+/// Magenta/black checkerboard, the classic "texture missing" look.
+pub fn placeholder_image(width: u16, height: u16) -> Image {
+    let mut img = Image::gen_image_color(width, height, BLACK);
+    for y in 0..height as u32 {
+        for x in 0..width as u32 {
+            if (x / 8 + y / 8) % 2 == 0 {
+                img.set_pixel(x, y, MAGENTA);
+            }
+        }
+    }
+    img
+}
+
+pub fn placeholder_texture(width: u16, height: u16) -> Texture2D {
+    let tex = Texture2D::from_image(&placeholder_image(width, height));
+    tex.set_filter(FilterMode::Nearest);
+    tex
+}
+
+/// Loads every map in `assets/maps/` and every map the manifest lists, sorted by name; a map's
+/// id is its index. One that is missing or broken is replaced by a placeholder in its own slot,
+/// so the ids after it stay the same.
+async fn load_maps() -> Vec<Sprite> {
+    let map_loc = Path::new(ASSET_LOC).join(MAP_LOC);
+    let mut paths = read_dir_sorted(&map_loc);
+    if let Ok(manifest) = Manifest::load() {
+        paths.extend(
+            manifest
+                .textures
+                .iter()
+                .filter(|el| el.starts_with(MAP_LOC))
+                .map(|el| Path::new(ASSET_LOC).join(el)),
+        );
+    }
+    paths.sort_unstable();
+    paths.dedup();
+    let mut maps = futures::future::join_all(paths.into_iter().map(load_map)).await;
+    if maps.is_empty() {
+        maps.push(placeholder_texture(
+            PLACEHOLDER_MAP_SIZE,
+            PLACEHOLDER_MAP_SIZE,
+        ));
+    }
+    maps.into_iter().map(Sprite::new).collect()
+}
+
+async fn load_map(path: PathBuf) -> Texture2D {
+    match load_texture(&path.to_string_lossy()).await {
+        Ok(tex) => {
+            tex.set_filter(FilterMode::Nearest);
+            tex
+        }
+        Err(e) => {
+            error!("Using placeholder for map {}: {e}", path.display());
+            placeholder_texture(PLACEHOLDER_MAP_SIZE, PLACEHOLDER_MAP_SIZE)
+        }
+    }
+}
+
+/// Seconds each player animation frame is shown.
+const PLAYER_FRAME_TIME: f32 = 0.1;
+
+/// Loads `assets/player_anim/`, where files are named `<clip><frame>.png`, e.g. `walk3.png`.
+async fn load_player_clips() -> Clips<Sprite> {
     let mut player_anim_loc = PathBuf::from(ASSET_LOC);
-    player_anim_loc.push(ENEMY_LOC);
-    let mut handles = Vec::new();
-    for i in player_anim_loc.read_dir().unwrap() {
-        handles.push(gen_loader(i.unwrap().path()));
+    player_anim_loc.push(PLAYER_ANIM_LOC);
+    let mut frames = Vec::new();
+    for path in read_dir_sorted(&player_anim_loc) {
+        let parsed = path
+            .file_stem()
+            .and_then(|el| el.to_str())
+            .and_then(parse_frame_name)
+            .and_then(|(_, name, frame)| Some((AnimState::from_name(name)?, frame)));
+        match parsed {
+            Some((state, frame)) => {
+                frames.push((state, frame, Sprite::new(gen_loader(path).await)))
+            }
+            None => warn!(
+                "Skipping player frame with unexpected name: {}",
+                path.display()
+            ),
+        }
+    }
+    frames.sort_unstable_by_key(|(_, frame, _)| *frame);
+
+    let mut clips = Clips::new();
+    for state in AnimState::ALL {
+        let clip_frames: Vec<_> = frames
+            .iter()
+            .filter(|(s, _, _)| *s == state)
+            .map(|(_, _, sprite)| (PLAYER_FRAME_TIME, sprite.clone()))
+            .collect();
+        if clip_frames.is_empty() {
+            continue;
+        }
+        let len = clip_frames.len();
+        let mut clip = Clip::new(clip_frames, state.default_mode());
+        if state == AnimState::Walk {
+            // one step per foot: on the first frame and halfway through.
+            clip = clip.with_event(0, AnimEvent::Footstep);
+            if len > 1 {
+                clip = clip.with_event(len / 2, AnimEvent::Footstep);
+            }
+        }
+        clips.insert(state, clip);
     }
-    futures::future::join_all(handles).await
+    clips
 }
 
 #[allow(non_snake_case)]
@@ -91,21 +331,263 @@ pub async fn load_texture2D(path: PathBuf) -> Result<Texture2D> {
 #[allow(clippy::manual_async_fn)]
 fn gen_loader(path: PathBuf) -> impl std::future::Future<Output = Texture2D> {
     async move {
-        let tex = load_texture(path.to_str().unwrap()).await.unwrap();
+        let tex = match load_texture(&path.to_string_lossy()).await {
+            Ok(tex) => tex,
+            Err(e) => {
+                error!("Using placeholder for {}: {e}", path.display());
+                placeholder_texture(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE)
+            }
+        };
         tex.set_filter(FilterMode::Nearest);
         tex
     }
 }
 
-pub fn get_map(id: u32) -> Result<&'static Texture2D> {
-    MAPS.get(id as usize)
-        .ok_or(GameError::AssetLoadFailure(format!("MAP {id} NOT FOUND")))
+/// Splits an animation frame file stem such as `0idle3` into kind id (`0`), clip name (`idle`)
+/// and frame index (`3`). The id is optional, a missing frame index means frame 0.
+pub fn parse_frame_name(stem: &str) -> Option<(Option<u32>, &str, usize)> {
+    let name_start = stem
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(stem.len());
+    let name_end = stem
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(name_start, |i| i + 1);
+    let id = match &stem[..name_start] {
+        "" => None,
+        digits => Some(digits.parse().ok()?),
+    };
+    let name = &stem[name_start..name_end];
+    if name.is_empty() {
+        return None;
+    }
+    let frame = match &stem[name_end..] {
+        "" => 0,
+        digits => digits.parse().ok()?,
+    };
+    Some((id, name, frame))
+}
+
+async fn load_crosshair() -> Sprite {
+    let mut asset_loc = PathBuf::from(ASSET_LOC);
+    asset_loc.push(CROSSHAIR_LOC);
+    Sprite::new(gen_loader(asset_loc).await)
+}
+
+/// Widest an atlas gets before frames wrap onto a new row.
+const ATLAS_WIDTH: u16 = 1024;
+/// Transparent gap between packed frames so neighbours never bleed into each other.
+const ATLAS_PADDING: u16 = 1;
+/// Largest atlas side. Every GPU the game targets supports textures this big.
+const MAX_ATLAS_SIZE: u16 = 4096;
+
+/// Many small images packed into a single texture, so every draw from it can be batched.
+pub struct TextureAtlas {
+    pub texture: Texture2D,
+    /// Source rectangle of every packed image, in the order they were given to `pack`.
+    pub rects: Vec<Rect>,
+}
+
+impl TextureAtlas {
+    /// Packs `images` into rows (shelves) left to right, top to bottom.
+    pub fn pack(images: &[Image]) -> Result<Self> {
+        let sizes: Vec<_> = images.iter().map(|img| (img.width, img.height)).collect();
+        let (width, height, rects) = atlas_layout(&sizes)?;
+
+        let mut atlas = Image::gen_image_color(width, height, BLANK);
+        for (img, rect) in images.iter().zip(&rects) {
+            let row_len = img.width as usize * 4;
+            for row in 0..img.height as usize {
+                let src = row * row_len;
+                let dst = ((rect.y as usize + row) * width as usize + rect.x as usize) * 4;
+                atlas.bytes[dst..dst + row_len].copy_from_slice(&img.bytes[src..src + row_len]);
+            }
+        }
+        let texture = Texture2D::from_image(&atlas);
+        texture.set_filter(FilterMode::Nearest);
+        Ok(TextureAtlas { texture, rects })
+    }
+}
+
+/// Width, height and the rect of every image for an atlas of images of `sizes`, as
+/// `TextureAtlas::pack` lays them out. Fails if a side would exceed `MAX_ATLAS_SIZE`.
+pub fn atlas_layout(sizes: &[(u16, u16)]) -> Result<(u16, u16, Vec<Rect>)> {
+    let too_large = || {
+        GameError::AssetLoadFailure(format!(
+            "{} images don't fit in a {MAX_ATLAS_SIZE}x{MAX_ATLAS_SIZE} atlas",
+            sizes.len()
+        ))
+    };
+    let width = sizes
+        .iter()
+        .map(|(w, _)| w.checked_add(ATLAS_PADDING))
+        .try_fold(ATLAS_WIDTH, |acc, el| el.map(|el| acc.max(el)))
+        .filter(|el| *el <= MAX_ATLAS_SIZE)
+        .ok_or_else(too_large)?;
+    let mut rects = Vec::with_capacity(sizes.len());
+    let (mut x, mut y, mut row_height) = (0u16, 0u16, 0u16);
+    for &(w, h) in sizes {
+        // `width` already fits every image plus padding, so these can't overflow.
+        if x + w > width {
+            x = 0;
+            y = row_height
+                .checked_add(ATLAS_PADDING)
+                .and_then(|el| el.checked_add(y))
+                .ok_or_else(too_large)?;
+            row_height = 0;
+        }
+        rects.push(Rect::new(x as f32, y as f32, w as f32, h as f32));
+        x += w + ATLAS_PADDING;
+        row_height = row_height.max(h);
+    }
+    let height = y
+        .checked_add(row_height)
+        .filter(|el| *el <= MAX_ATLAS_SIZE)
+        .ok_or_else(too_large)?
+        .max(1);
+    Ok((width, height, rects))
+}
+
+/// Seconds between scans of `ASSET_LOC` for changed files.
+#[cfg(debug_assertions)]
+const WATCH_INTERVAL: f64 = 0.5;
+
+/// Polls `ASSET_LOC` for files modified since the last scan. Debug builds only.
+#[cfg(debug_assertions)]
+pub struct AssetWatcher {
+    mtimes: HashMap<PathBuf, SystemTime>,
+    last_scan: f64,
+}
+
+#[cfg(debug_assertions)]
+impl AssetWatcher {
+    /// Takes a snapshot of the current files, so nothing counts as changed on the first poll.
+    pub fn new() -> Self {
+        let mut watcher = AssetWatcher {
+            mtimes: HashMap::new(),
+            last_scan: 0.,
+        };
+        watcher.scan();
+        watcher
+    }
+
+    /// Files created or modified since the last scan. Scans at most every `WATCH_INTERVAL`
+    /// of `now`, in seconds as given by `Clock::time`.
+    pub fn poll(&mut self, now: f64) -> Vec<PathBuf> {
+        if now - self.last_scan < WATCH_INTERVAL {
+            return Vec::new();
+        }
+        self.last_scan = now;
+        self.scan()
+    }
+
+    fn scan(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        let mut dirs = vec![PathBuf::from(ASSET_LOC)];
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = dir.read_dir() else {
+                continue;
+            };
+            for entry in entries.filter_map(core::result::Result::ok) {
+                let path = entry.path();
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if meta.is_dir() {
+                    dirs.push(path);
+                } else if let Ok(mtime) = meta.modified()
+                    && self.mtimes.insert(path.clone(), mtime) != Some(mtime)
+                {
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
 }
 
-pub static CROSSHAIR_TEX: Lazy<Texture2D> = Lazy::new(|| block_on(load_crosshair()));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_reads_headers_and_reports_every_problem() {
+        let manifest = Manifest {
+            textures: vec![
+                CROSSHAIR_LOC.into(),
+                "missing.png".into(),
+                MANIFEST_LOC.into(),
+            ],
+            data: vec![MANIFEST_LOC.into(), CROSSHAIR_LOC.into()],
+        };
+        let problems = manifest.validate();
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(matches!(problems[0], GameError::NotFoundError(_)));
+    }
+
+    #[test]
+    fn parse_frame_name_splits_id_name_and_frame() {
+        assert_eq!(parse_frame_name("0idle3"), Some((Some(0), "idle", 3)));
+        assert_eq!(parse_frame_name("12walk07"), Some((Some(12), "walk", 7)));
+        // player frames have no kind id.
+        assert_eq!(parse_frame_name("death10"), Some((None, "death", 10)));
+        // digits inside the name belong to it.
+        assert_eq!(parse_frame_name("1hit2b4"), Some((Some(1), "hit2b", 4)));
+    }
+
+    #[test]
+    fn parse_frame_name_defaults_a_missing_frame_to_zero() {
+        assert_eq!(parse_frame_name("idle"), Some((None, "idle", 0)));
+        assert_eq!(parse_frame_name("3hurt"), Some((Some(3), "hurt", 0)));
+    }
 
-async fn load_crosshair() -> Texture2D {
-    let mut asset_loc = ASSET_LOC.to_owned();
-    asset_loc.push_str("crosshair.png");
-    load_texture(&asset_loc).await.unwrap()
+    #[test]
+    fn parse_frame_name_rejects_malformed_stems() {
+        assert_eq!(parse_frame_name(""), None);
+        assert_eq!(parse_frame_name("42"), None);
+        // out of range for the id or the frame index.
+        assert_eq!(parse_frame_name("99999999999idle"), None);
+        assert_eq!(parse_frame_name("idle99999999999999999999999"), None);
+    }
+
+    #[test]
+    fn atlas_layout_packs_without_overlap() {
+        let sizes: Vec<_> = (0..300u16)
+            .map(|i| (8 + i * 7 % 90, 8 + i * 13 % 70))
+            .collect();
+        let (width, height, rects) = atlas_layout(&sizes).unwrap();
+        assert_eq!(width, ATLAS_WIDTH);
+        assert_eq!(rects.len(), sizes.len());
+        for (i, (a, &(w, h))) in rects.iter().zip(&sizes).enumerate() {
+            assert_eq!(a.size(), vec2(w as f32, h as f32));
+            assert!(a.right() <= width as f32 && a.bottom() <= height as f32);
+            for b in &rects[i + 1..] {
+                // padding keeps even neighbouring frames apart.
+                let pad = ATLAS_PADDING as f32;
+                let apart = a.right() + pad <= b.x
+                    || b.right() + pad <= a.x
+                    || a.bottom() + pad <= b.y
+                    || b.bottom() + pad <= a.y;
+                assert!(apart, "{a:?} {b:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn atlas_layout_wraps_rows_and_grows_for_wide_images() {
+        let (_, _, rects) = atlas_layout(&[(600, 10), (600, 20), (10, 5)]).unwrap();
+        assert_eq!(rects[1].y, 10. + ATLAS_PADDING as f32);
+        assert_eq!(rects[2].x, 600. + ATLAS_PADDING as f32);
+        let (width, _, _) = atlas_layout(&[(2000, 10)]).unwrap();
+        assert_eq!(width, 2000 + ATLAS_PADDING);
+        assert_eq!(atlas_layout(&[]).unwrap(), (ATLAS_WIDTH, 1, Vec::new()));
+    }
+
+    #[test]
+    fn atlas_layout_rejects_oversized_atlases() {
+        assert!(atlas_layout(&[(MAX_ATLAS_SIZE, 10)]).is_err());
+        // would overflow u16 rather than just exceed the limit.
+        assert!(atlas_layout(&[(u16::MAX, 10)]).is_err());
+        assert!(atlas_layout(&[(10, u16::MAX)]).is_err());
+        assert!(atlas_layout(&[(ATLAS_WIDTH, 1000); 10]).is_err());
+    }
 }
@@ -0,0 +1,307 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use macroquad::{
+    audio::{PlaySoundParams, Sound, load_sound, play_sound, set_sound_volume, stop_sound},
+    prelude::*,
+};
+
+use crate::{
+    assets::{ASSET_LOC, read_dir_sorted},
+    settings::Settings,
+};
+
+const SFX_LOC: &str = "sfx";
+const MUSIC_LOC: &str = "music";
+
+/// Sound effects, loaded from `assets/sfx/<name>.wav` or `.ogg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sfx {
+    Swing,
+    Hit,
+    /// Played instead of `Hit` when the swing crits.
+    Crit,
+    PlayerHurt,
+    EnemyDeath,
+    LevelStart,
+    Footstep,
+}
+
+impl Sfx {
+    pub const ALL: [Sfx; 7] = [
+        Self::Swing,
+        Self::Hit,
+        Self::Crit,
+        Self::PlayerHurt,
+        Self::EnemyDeath,
+        Self::LevelStart,
+        Self::Footstep,
+    ];
+
+    /// File stem under `SFX_LOC`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Swing => "swing",
+            Self::Hit => "hit",
+            Self::Crit => "crit",
+            Self::PlayerHurt => "player_hurt",
+            Self::EnemyDeath => "enemy_death",
+            Self::LevelStart => "level_start",
+            Self::Footstep => "footstep",
+        }
+    }
+}
+
+/// Something that can make noise. Volumes passed in already include the master bus.
+pub trait AudioBackend {
+    fn play_sfx(&mut self, sfx: Sfx, volume: f32);
+    /// Starts looping the music of `map`, replacing whatever played before.
+    fn play_music(&mut self, map: u32, volume: f32);
+    fn stop_music(&mut self);
+    fn set_music_volume(&mut self, volume: f32);
+}
+
+/// Plays nothing. Used when there is no audio device, and for running the model without one.
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn play_sfx(&mut self, _sfx: Sfx, _volume: f32) {}
+    fn play_music(&mut self, _map: u32, _volume: f32) {}
+    fn stop_music(&mut self) {}
+    fn set_music_volume(&mut self, _volume: f32) {}
+}
+
+/// Plays through macroquad. Without the `audio` feature, macroquad swaps in a silent stub.
+pub struct MacroquadAudio {
+    sfx: HashMap<Sfx, Sound>,
+    /// Music by map id, from `assets/music/<map id>.ogg`.
+    music: HashMap<u32, Sound>,
+    playing: Option<u32>,
+}
+
+impl MacroquadAudio {
+    /// Loads every sound that exists. Missing ones are skipped, so the game just stays quiet.
+    pub async fn load() -> Self {
+        let mut sfx = HashMap::new();
+        let sfx_files = sound_files(SFX_LOC);
+        for kind in Sfx::ALL {
+            let Some(path) = sfx_files
+                .iter()
+                .find(|el| el.file_stem().is_some_and(|stem| stem == kind.name()))
+            else {
+                continue;
+            };
+            match load_sound(&path.to_string_lossy()).await {
+                Ok(sound) => {
+                    sfx.insert(kind, sound);
+                }
+                Err(e) => warn!("Failed to load {}: {e}", path.display()),
+            }
+        }
+        let mut music = HashMap::new();
+        for path in sound_files(MUSIC_LOC) {
+            let Some(map) = path
+                .file_stem()
+                .and_then(|el| el.to_str())
+                .and_then(|el| el.parse().ok())
+            else {
+                continue;
+            };
+            match load_sound(&path.to_string_lossy()).await {
+                Ok(sound) => {
+                    music.insert(map, sound);
+                }
+                Err(e) => warn!("Failed to load {}: {e}", path.display()),
+            }
+        }
+        MacroquadAudio {
+            sfx,
+            music,
+            playing: None,
+        }
+    }
+}
+
+/// Files in `ASSET_LOC/dir`. Sounds are optional, so a missing directory is not an error.
+fn sound_files(dir: &str) -> Vec<PathBuf> {
+    let dir = PathBuf::from(ASSET_LOC).join(dir);
+    if dir.is_dir() {
+        read_dir_sorted(&dir)
+    } else {
+        Vec::new()
+    }
+}
+
+impl AudioBackend for MacroquadAudio {
+    fn play_sfx(&mut self, sfx: Sfx, volume: f32) {
+        if let Some(sound) = self.sfx.get(&sfx) {
+            play_sound(
+                sound,
+                PlaySoundParams {
+                    looped: false,
+                    volume,
+                },
+            );
+        }
+    }
+    fn play_music(&mut self, map: u32, volume: f32) {
+        if self.playing == Some(map) {
+            return;
+        }
+        self.stop_music();
+        if let Some(sound) = self.music.get(&map) {
+            play_sound(
+                sound,
+                PlaySoundParams {
+                    looped: true,
+                    volume,
+                },
+            );
+            self.playing = Some(map);
+        }
+    }
+    fn stop_music(&mut self) {
+        if let Some(sound) = self.playing.take().and_then(|el| self.music.get(&el)) {
+            stop_sound(sound);
+        }
+    }
+    fn set_music_volume(&mut self, volume: f32) {
+        if let Some(sound) = self.playing.and_then(|el| self.music.get(&el)) {
+            set_sound_volume(sound, volume);
+        }
+    }
+}
+
+/// Volume buses in `0..=1`. Music and effects are each scaled by master.
+#[derive(Debug, Clone, Copy)]
+pub struct Volumes {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl From<&Settings> for Volumes {
+    fn from(settings: &Settings) -> Self {
+        Volumes {
+            master: settings.master_volume,
+            music: settings.music_volume,
+            sfx: settings.sfx_volume,
+        }
+    }
+}
+
+/// What the model talks to. Mixes the buses and hands the result to a backend.
+pub struct AudioMan {
+    backend: Box<dyn AudioBackend>,
+    volumes: Volumes,
+}
+
+impl AudioMan {
+    pub fn new(backend: Box<dyn AudioBackend>, volumes: Volumes) -> Self {
+        AudioMan { backend, volumes }
+    }
+    pub fn play(&mut self, sfx: Sfx) {
+        let volume = self.volumes.master * self.volumes.sfx;
+        if volume > 0. {
+            self.backend.play_sfx(sfx, volume);
+        }
+    }
+    pub fn play_music(&mut self, map: u32) {
+        self.backend
+            .play_music(map, self.volumes.master * self.volumes.music);
+    }
+    pub fn stop_music(&mut self) {
+        self.backend.stop_music();
+    }
+    pub fn set_volumes(&mut self, volumes: Volumes) {
+        self.volumes = volumes;
+        self.backend
+            .set_music_volume(volumes.master * volumes.music);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    const VOLUMES: Volumes = Volumes {
+        master: 0.5,
+        music: 0.8,
+        sfx: 0.4,
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Sfx(Sfx, f32),
+        Music(u32, f32),
+        Stop,
+        MusicVolume(f32),
+    }
+
+    /// Writes down what it is asked to play.
+    struct Recorder(Rc<RefCell<Vec<Call>>>);
+
+    impl AudioBackend for Recorder {
+        fn play_sfx(&mut self, sfx: Sfx, volume: f32) {
+            self.0.borrow_mut().push(Call::Sfx(sfx, volume));
+        }
+        fn play_music(&mut self, map: u32, volume: f32) {
+            self.0.borrow_mut().push(Call::Music(map, volume));
+        }
+        fn stop_music(&mut self) {
+            self.0.borrow_mut().push(Call::Stop);
+        }
+        fn set_music_volume(&mut self, volume: f32) {
+            self.0.borrow_mut().push(Call::MusicVolume(volume));
+        }
+    }
+
+    #[test]
+    fn null_audio_takes_everything_silently() {
+        let mut audio = AudioMan::new(Box::new(NullAudio), VOLUMES);
+        for sfx in Sfx::ALL {
+            audio.play(sfx);
+        }
+        audio.play_music(3);
+        audio.set_volumes(Volumes {
+            master: 0.,
+            ..VOLUMES
+        });
+        audio.play(Sfx::Crit);
+        audio.stop_music();
+    }
+
+    #[test]
+    fn buses_are_scaled_by_master() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut audio = AudioMan::new(Box::new(Recorder(calls.clone())), VOLUMES);
+        audio.play(Sfx::Crit);
+        audio.play_music(1);
+        audio.set_volumes(Volumes {
+            master: 1.,
+            ..VOLUMES
+        });
+        audio.stop_music();
+        assert_eq!(
+            *calls.borrow(),
+            [
+                Call::Sfx(Sfx::Crit, 0.5 * 0.4),
+                Call::Music(1, 0.5 * 0.8),
+                Call::MusicVolume(0.8),
+                Call::Stop,
+            ]
+        );
+    }
+
+    #[test]
+    fn muted_effects_are_not_played() {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut audio = AudioMan::new(
+            Box::new(Recorder(calls.clone())),
+            Volumes { sfx: 0., ..VOLUMES },
+        );
+        audio.play(Sfx::Hit);
+        assert!(calls.borrow().is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    backtrace::Backtrace,
+    fmt::Write as _,
+    fs::{self, File},
+    io::Write,
+    panic::PanicHookInfo,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use macroquad::prelude::*;
+
+use crate::{
+    errors::{GameError, Result},
+    logging,
+    model::{GameMode, SAVE_LOC, save::SaveData},
+    util::{INTERRUPT, format_unix_time, unix_now},
+};
+
+pub const CRASH_LOC: &str = "crashes/";
+/// Log lines shown in the fatal error dialog. The report has all of `logging::RECENT_LINES`.
+const DIALOG_LOG_LINES: usize = 15;
+const REPORT_URL: &str = "https://github.com/shuntia/pixel_rebels";
+
+/// What the panic hook knows about the game. `GameModel::update` keeps it current.
+#[derive(Clone, Default)]
+pub struct CrashContext {
+    pub mode: Option<GameMode>,
+    /// Seed of macroquad's `rand`, which places spawns.
+    pub seed: u64,
+    /// The run in progress, autosaved on a crash. `None` outside of gameplay.
+    pub run: Option<SaveData>,
+}
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    mode: None,
+    seed: 0,
+    run: None,
+});
+
+/// Replaces the context the next crash report is written with.
+pub fn set_context(context: CrashContext) {
+    if let Ok(mut el) = CONTEXT.lock() {
+        *el = context;
+    }
+}
+
+/// The panic hook. Writes a report to `CRASH_LOC`, autosaves the run and tells the player,
+/// with a dialog if there is a display and on stderr otherwise. Must not panic itself.
+pub fn handle_panic(info: &PanicHookInfo) {
+    INTERRUPT.store(true, std::sync::atomic::Ordering::Release);
+    let payload = info.payload();
+    let msg = if let Some(s) = payload.downcast_ref::<GameError>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else if let Some(s) = payload.downcast_ref::<&'static str>() {
+        s.to_string()
+    } else {
+        "Unknown Error.".to_owned()
+    };
+    let loc = info
+        .location()
+        .map_or_else(|| "unknown".to_owned(), |el| el.to_string());
+    error!("panicked at {loc}: {msg}");
+
+    // the panic may have happened while the model held the lock, so don't wait for it.
+    let context = CONTEXT.try_lock().map(|el| el.clone()).unwrap_or_default();
+    let autosave = match &context.run {
+        Some(run) => match run.write_new(Path::new(SAVE_LOC)) {
+            Ok(path) => format!("saved to {}", path.display()),
+            Err(e) => format!("failed: {e}"),
+        },
+        None => "no run in progress".to_owned(),
+    };
+    let recent = logging::recent();
+    // one time for the header and the file name, so the report can be found by either.
+    let secs = unix_now().as_secs();
+    let report = build_report(secs, &msg, &loc, &context, &autosave, &recent);
+    let saved = match write_report(&report, secs) {
+        Ok(path) => format!("A crash report was saved to {}.", path.display()),
+        Err(e) => format!("The crash report could not be saved: {e}"),
+    };
+    logging::flush();
+
+    let shown = &recent[recent.len().saturating_sub(DIALOG_LOG_LINES)..];
+    let text = format!(
+        "FATAL ERROR:\n{msg}\nLocation: {loc}\n\nAutosave: {autosave}\n\nLast log lines:\n{}\n\n{saved}\nPlease report this at {REPORT_URL}",
+        shown.join("\n")
+    );
+    let shown = has_display()
+        && native_dialog::DialogBuilder::message()
+            .set_text(&text)
+            .set_level(native_dialog::MessageLevel::Error)
+            .set_title(":(")
+            .alert()
+            .show()
+            .is_ok();
+    if !shown {
+        eprintln!("{text}");
+    }
+}
+
+/// Whether a dialog can be shown. Headless Linux, e.g. CI or ssh, has no display to show it on.
+fn has_display() -> bool {
+    if cfg!(target_os = "linux") {
+        ["DISPLAY", "WAYLAND_DISPLAY"]
+            .iter()
+            .any(|el| std::env::var_os(el).is_some_and(|el| !el.is_empty()))
+    } else {
+        true
+    }
+}
+
+/// `secs` is the time of the crash, in seconds since the unix epoch.
+fn build_report(
+    secs: u64,
+    msg: &str,
+    loc: &str,
+    context: &CrashContext,
+    autosave: &str,
+    recent: &[String],
+) -> String {
+    let mut report = String::new();
+    // writing to a String can't fail.
+    let _ = writeln!(report, "{} crash report", env!("CARGO_PKG_NAME"));
+    let _ = writeln!(report, "time: {} UTC", format_unix_time(secs));
+    let _ = writeln!(report, "build: {}", build_info());
+    let _ = writeln!(report, "message: {msg}");
+    let _ = writeln!(report, "location: {loc}");
+    let _ = writeln!(report, "mode: {:?}", context.mode);
+    let _ = writeln!(report, "seed: {}", context.seed);
+    let _ = writeln!(report, "autosave: {autosave}");
+    let _ = writeln!(report, "\nbacktrace:\n{}", Backtrace::force_capture());
+    let _ = writeln!(report, "last {} log lines:", recent.len());
+    for line in recent {
+        let _ = writeln!(report, "{line}");
+    }
+    report
+}
+
+/// Version, profile, platform and enabled features.
+pub fn build_info() -> String {
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let features: Vec<_> = [
+        ("audio", cfg!(feature = "audio")),
+        ("gamepad", cfg!(feature = "gamepad")),
+    ]
+    .into_iter()
+    .filter_map(|(name, on)| on.then_some(name))
+    .collect();
+    let features = if features.is_empty() {
+        "none".to_owned()
+    } else {
+        features.join(", ")
+    };
+    format!(
+        "{} {} ({profile}, {} {}, features: {features})",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+    )
+}
+
+/// Writes `report` to `CRASH_LOC/crash-<secs>.txt`. Returns its path.
+fn write_report(report: &str, secs: u64) -> Result<PathBuf> {
+    fs::create_dir_all(CRASH_LOC)?;
+    let path = Path::new(CRASH_LOC).join(format!("crash-{secs}.txt"));
+    File::create(&path)?.write_all(report.as_bytes())?;
+    Ok(path)
+}
@@ -26,6 +26,12 @@ pub enum GameError {
     Unexpected(String),
     #[error("Received Illegal Arguments!!! This should not happen!!! Message: {0}")]
     IllegalArgument(String),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("Invalid save: {0}")]
+    InvalidSave(String),
+    #[error("Invalid command: {0}")]
+    InvalidCommand(String),
     #[error("Macroquad Error: {0}")]
     MQError(#[from] Error),
     #[error("Miscellaneous Error: {0:?}")]
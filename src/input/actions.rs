@@ -0,0 +1,484 @@
+use std::{collections::HashMap, fmt::Display, fs::File, path::Path, str::FromStr};
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    errors::{GameError, Nresult, Result},
+    input::gamepad::PadButton,
+};
+
+pub const BINDINGS_LOC: &str = "bindings.json";
+
+/// Something the player can do, independent of which key does it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Attack,
+    Dash,
+    Pause,
+    ToggleDebug,
+    ToggleConsole,
+    ToggleLetterbox,
+    ToggleHitboxes,
+    ToggleWeaponCone,
+    ToggleGrid,
+    DebugSpawn,
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    MenuConfirm,
+    MenuBack,
+    MenuDelete,
+    MenuRename,
+    OpenSettings,
+}
+
+/// Actions that are read at the same time. A binding may only be used once per context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Gameplay,
+    Menu,
+}
+
+impl Action {
+    pub const ALL: [Action; 23] = [
+        Self::MoveUp,
+        Self::MoveDown,
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::Attack,
+        Self::Dash,
+        Self::Pause,
+        Self::ToggleDebug,
+        Self::ToggleConsole,
+        Self::ToggleLetterbox,
+        Self::ToggleHitboxes,
+        Self::ToggleWeaponCone,
+        Self::ToggleGrid,
+        Self::DebugSpawn,
+        Self::MenuUp,
+        Self::MenuDown,
+        Self::MenuLeft,
+        Self::MenuRight,
+        Self::MenuConfirm,
+        Self::MenuBack,
+        Self::MenuDelete,
+        Self::MenuRename,
+        Self::OpenSettings,
+    ];
+
+    pub fn context(self) -> Context {
+        match self {
+            Self::MenuUp
+            | Self::MenuDown
+            | Self::MenuLeft
+            | Self::MenuRight
+            | Self::MenuConfirm
+            | Self::MenuBack
+            | Self::MenuDelete
+            | Self::MenuRename
+            | Self::OpenSettings => Context::Menu,
+            _ => Context::Gameplay,
+        }
+    }
+
+    /// Whether `self` and `other` are read at the same time, so can't share a binding.
+    pub fn clashes_with(self, other: Action) -> bool {
+        match (self, other) {
+            _ if self == other => false,
+            // both leave the pause menu, so sharing a key is harmless.
+            (Self::Pause, Self::MenuBack) | (Self::MenuBack, Self::Pause) => false,
+            // the pause menu reads `Pause` together with the menu actions.
+            (Self::Pause, _) | (_, Self::Pause) => true,
+            _ => self.context() == other.context(),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::MoveUp => "Move up",
+            Self::MoveDown => "Move down",
+            Self::MoveLeft => "Move left",
+            Self::MoveRight => "Move right",
+            Self::Attack => "Attack",
+            Self::Dash => "Dash",
+            Self::Pause => "Pause",
+            Self::ToggleDebug => "Debug overlay",
+            Self::ToggleConsole => "Console (debug)",
+            Self::ToggleLetterbox => "Letterbox",
+            Self::ToggleHitboxes => "Show hitboxes (debug)",
+            Self::ToggleWeaponCone => "Show weapon cone (debug)",
+            Self::ToggleGrid => "Show grid (debug)",
+            Self::DebugSpawn => "Spawn enemies (debug)",
+            Self::MenuUp => "Menu up",
+            Self::MenuDown => "Menu down",
+            Self::MenuLeft => "Menu left",
+            Self::MenuRight => "Menu right",
+            Self::MenuConfirm => "Menu confirm",
+            Self::MenuBack => "Menu back",
+            Self::MenuDelete => "Delete save",
+            Self::MenuRename => "Rename save",
+            Self::OpenSettings => "Open settings",
+        }
+    }
+
+    fn default_bindings(self) -> Vec<Binding> {
+        use Binding::*;
+        match self {
+            Self::MoveUp => vec![Key(KeyCode::W)],
+            Self::MoveDown => vec![Key(KeyCode::S)],
+            Self::MoveLeft => vec![Key(KeyCode::A)],
+            Self::MoveRight => vec![Key(KeyCode::D)],
+            Self::Attack => vec![Mouse(MouseButton::Left), Pad(PadButton::RightTrigger)],
+            Self::Dash => vec![Key(KeyCode::LeftShift), Pad(PadButton::RightShoulder)],
+            Self::Pause => vec![Key(KeyCode::Escape), Pad(PadButton::Start)],
+            Self::ToggleDebug => vec![Key(KeyCode::Slash)],
+            Self::ToggleConsole => vec![Key(KeyCode::GraveAccent)],
+            Self::ToggleLetterbox => vec![Key(KeyCode::F2)],
+            Self::ToggleHitboxes => vec![Key(KeyCode::F3)],
+            Self::ToggleWeaponCone => vec![Key(KeyCode::F4)],
+            Self::ToggleGrid => vec![Key(KeyCode::F5)],
+            Self::DebugSpawn => vec![Key(KeyCode::Space)],
+            Self::MenuUp => vec![Key(KeyCode::Up), Pad(PadButton::DPadUp)],
+            Self::MenuDown => vec![Key(KeyCode::Down), Pad(PadButton::DPadDown)],
+            Self::MenuLeft => vec![Key(KeyCode::Left), Pad(PadButton::DPadLeft)],
+            Self::MenuRight => vec![Key(KeyCode::Right), Pad(PadButton::DPadRight)],
+            Self::MenuConfirm => vec![Key(KeyCode::Enter), Pad(PadButton::South)],
+            Self::MenuBack => vec![Key(KeyCode::Escape), Pad(PadButton::East)],
+            Self::MenuDelete => vec![Key(KeyCode::Delete), Pad(PadButton::West)],
+            Self::MenuRename => vec![Key(KeyCode::F2), Pad(PadButton::North)],
+            Self::OpenSettings => vec![Key(KeyCode::Tab)],
+        }
+    }
+}
+
+/// A physical input an action can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Pad(PadButton),
+}
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        /// Keys that can be bound, with the names used in `BINDINGS_LOC`.
+        const KEY_NAMES: &[(KeyCode, &str)] = &[$((KeyCode::$key, stringify!($key))),*];
+    };
+}
+
+key_names!(
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Key0,
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    Space,
+    Enter,
+    Escape,
+    Tab,
+    Backspace,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Up,
+    Down,
+    Left,
+    Right,
+    LeftShift,
+    RightShift,
+    LeftControl,
+    RightControl,
+    LeftAlt,
+    RightAlt,
+    Slash,
+    Backslash,
+    Comma,
+    Period,
+    Semicolon,
+    Apostrophe,
+    Minus,
+    Equal,
+    LeftBracket,
+    RightBracket,
+    GraveAccent,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+);
+
+const MOUSE_NAMES: &[(MouseButton, &str)] = &[
+    (MouseButton::Left, "Left"),
+    (MouseButton::Middle, "Middle"),
+    (MouseButton::Right, "Right"),
+];
+
+impl Binding {
+    /// Short name for on-screen hints, e.g. `Enter` or `Pad South`.
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => match KEY_NAMES.iter().find(|(k, _)| k == key) {
+                Some((_, name)) => (*name).to_owned(),
+                None => format!("{key:?}"),
+            },
+            Binding::Mouse(button) => match MOUSE_NAMES.iter().find(|(b, _)| b == button) {
+                Some((_, name)) => format!("{name} click"),
+                None => format!("{button:?}"),
+            },
+            Binding::Pad(button) => format!("Pad {button:?}"),
+        }
+    }
+    pub fn is_pad(&self) -> bool {
+        matches!(self, Binding::Pad(_))
+    }
+}
+
+impl Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binding::Key(key) => match KEY_NAMES.iter().find(|(k, _)| k == key) {
+                Some((_, name)) => write!(f, "Key:{name}"),
+                None => write!(f, "Key:{key:?}"),
+            },
+            Binding::Mouse(button) => match MOUSE_NAMES.iter().find(|(b, _)| b == button) {
+                Some((_, name)) => write!(f, "Mouse:{name}"),
+                None => write!(f, "Mouse:{button:?}"),
+            },
+            Binding::Pad(button) => write!(f, "Pad:{button:?}"),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = GameError;
+    fn from_str(s: &str) -> Result<Self> {
+        let unknown = || GameError::InvalidConfig(format!("unknown binding {s:?}"));
+        match s.split_once(':') {
+            Some(("Key", name)) => KEY_NAMES
+                .iter()
+                .find(|(_, n)| *n == name)
+                .map(|(k, _)| Binding::Key(*k))
+                .ok_or_else(unknown),
+            Some(("Mouse", name)) => MOUSE_NAMES
+                .iter()
+                .find(|(_, n)| *n == name)
+                .map(|(b, _)| Binding::Mouse(*b))
+                .ok_or_else(unknown),
+            Some(("Pad", name)) => PadButton::ALL
+                .into_iter()
+                .find(|b| format!("{b:?}") == name)
+                .map(Binding::Pad)
+                .ok_or_else(unknown),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+impl Serialize for Binding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> core::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Binding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> core::result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Which bindings trigger which action. Loaded from and saved to `BINDINGS_LOC`.
+#[derive(Serialize, Deserialize)]
+pub struct ActionMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        ActionMap {
+            bindings: Action::ALL
+                .into_iter()
+                .map(|action| (action, action.default_bindings()))
+                .collect(),
+        }
+    }
+}
+
+impl ActionMap {
+    /// Reads `path`, filling in defaults for actions the file doesn't mention.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|_| GameError::NotFoundError(path.to_path_buf()))?;
+        let mut map: ActionMap = serde_json::from_reader(file)
+            .map_err(|e| GameError::InvalidConfig(format!("{}: {e}", path.display())))?;
+        for action in Action::ALL {
+            map.bindings
+                .entry(action)
+                .or_insert_with(|| action.default_bindings());
+        }
+        Ok(map)
+    }
+
+    /// Loads `BINDINGS_LOC`, falling back to the defaults if it is missing or broken.
+    pub fn load_or_default() -> Self {
+        Self::load(Path::new(BINDINGS_LOC)).unwrap_or_else(|e| {
+            if !matches!(e, GameError::NotFoundError(_)) {
+                error!("Using default bindings: {e}");
+            }
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Nresult {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| GameError::Unexpected(format!("{}: {e}", path.display())))
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The bindings of `action` for on-screen hints, e.g. `Enter/Pad South`.
+    pub fn hint(&self, action: Action) -> String {
+        let labels: Vec<_> = self.get(action).iter().map(Binding::label).collect();
+        if labels.is_empty() {
+            "(unbound)".to_owned()
+        } else {
+            labels.join("/")
+        }
+    }
+
+    /// Another action read at the same time already using `binding`, if any.
+    pub fn conflict(&self, action: Action, binding: Binding) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|other| action.clashes_with(*other) && self.get(*other).contains(&binding))
+    }
+
+    /// Makes `binding` the binding of `action` on its device, keyboard and mouse or gamepad.
+    /// Bindings on the other device are kept. Refuses bindings that would conflict.
+    pub fn rebind(&mut self, action: Action, binding: Binding) -> core::result::Result<(), Action> {
+        if let Some(other) = self.conflict(action, binding) {
+            return Err(other);
+        }
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|el| el.is_pad() != binding.is_pad());
+        bindings.push(binding);
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let map = ActionMap::default();
+        for action in Action::ALL {
+            for binding in map.get(action) {
+                assert_eq!(map.conflict(action, *binding), None, "{action:?} {binding}");
+            }
+        }
+    }
+
+    #[test]
+    fn rebind_keeps_the_other_device() {
+        let mut map = ActionMap::default();
+        map.rebind(Action::Attack, Binding::Key(KeyCode::J))
+            .unwrap();
+        assert_eq!(
+            map.get(Action::Attack),
+            [
+                Binding::Pad(PadButton::RightTrigger),
+                Binding::Key(KeyCode::J)
+            ]
+        );
+        map.rebind(Action::Attack, Binding::Pad(PadButton::LeftTrigger))
+            .unwrap();
+        assert_eq!(
+            map.get(Action::Attack),
+            [
+                Binding::Key(KeyCode::J),
+                Binding::Pad(PadButton::LeftTrigger)
+            ]
+        );
+    }
+
+    #[test]
+    fn pause_conflicts_with_menu_actions() {
+        let mut map = ActionMap::default();
+        assert_eq!(
+            map.rebind(Action::Pause, Binding::Key(KeyCode::Enter)),
+            Err(Action::MenuConfirm)
+        );
+        assert_eq!(
+            map.rebind(Action::MenuDelete, Binding::Pad(PadButton::Start)),
+            Err(Action::Pause)
+        );
+        // menu actions still don't clash with gameplay ones.
+        assert!(map.rebind(Action::MenuUp, Binding::Key(KeyCode::W)).is_ok());
+    }
+
+    #[test]
+    fn bindings_round_trip_through_strings() {
+        for action in Action::ALL {
+            for binding in action.default_bindings() {
+                assert_eq!(binding.to_string().parse::<Binding>().unwrap(), binding);
+            }
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashSet;
+#[cfg(test)]
+use std::collections::VecDeque;
+
+use macroquad::prelude::*;
+
+/// Sticks closer to center than this count as released.
+pub const DEADZONE: f32 = 0.2;
+/// How far a trigger has to be pulled to count as pressed.
+const TRIGGER_THRESHOLD: f32 = 0.5;
+
+/// Digital gamepad inputs, named by position rather than label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PadButton {
+    South,
+    East,
+    West,
+    North,
+    Start,
+    Select,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    LeftShoulder,
+    RightShoulder,
+    /// Left trigger pulled past `TRIGGER_THRESHOLD`.
+    LeftTrigger,
+    /// Right trigger pulled past `TRIGGER_THRESHOLD`.
+    RightTrigger,
+}
+
+impl PadButton {
+    pub const ALL: [PadButton; 14] = [
+        Self::South,
+        Self::East,
+        Self::West,
+        Self::North,
+        Self::Start,
+        Self::Select,
+        Self::DPadUp,
+        Self::DPadDown,
+        Self::DPadLeft,
+        Self::DPadRight,
+        Self::LeftShoulder,
+        Self::RightShoulder,
+        Self::LeftTrigger,
+        Self::RightTrigger,
+    ];
+}
+
+/// Snapshot of one gamepad. Stick y points down, like screen and world coordinates.
+#[derive(Debug, Clone, Default)]
+pub struct GamepadState {
+    pub left_stick: Vec2,
+    pub right_stick: Vec2,
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    pub buttons: HashSet<PadButton>,
+}
+
+impl GamepadState {
+    /// Buttons held, with the triggers turned into buttons.
+    fn held(&self) -> HashSet<PadButton> {
+        let mut held = self.buttons.clone();
+        if self.left_trigger >= TRIGGER_THRESHOLD {
+            held.insert(PadButton::LeftTrigger);
+        }
+        if self.right_trigger >= TRIGGER_THRESHOLD {
+            held.insert(PadButton::RightTrigger);
+        }
+        held
+    }
+}
+
+/// Where gamepad state comes from. Backends implement this, `GamepadMan` does the rest.
+pub trait GamepadSource {
+    /// Current state of the pad, `None` while none is connected.
+    fn poll(&mut self) -> Option<GamepadState>;
+}
+
+/// Used when no gamepad backend is available. Never connected.
+pub struct NullGamepad;
+
+impl GamepadSource for NullGamepad {
+    fn poll(&mut self) -> Option<GamepadState> {
+        None
+    }
+}
+
+/// Reads the most recently used gamepad through gilrs.
+#[cfg(feature = "gamepad")]
+pub struct GilrsGamepad {
+    gilrs: gilrs::Gilrs,
+    /// The pad that sent the last event. Falls back to any connected pad.
+    active: Option<gilrs::GamepadId>,
+}
+
+/// gilrs buttons and the pad buttons they map to. Triggers are read as axes instead.
+#[cfg(feature = "gamepad")]
+const GILRS_BUTTONS: [(gilrs::Button, PadButton); 12] = [
+    (gilrs::Button::South, PadButton::South),
+    (gilrs::Button::East, PadButton::East),
+    (gilrs::Button::West, PadButton::West),
+    (gilrs::Button::North, PadButton::North),
+    (gilrs::Button::Start, PadButton::Start),
+    (gilrs::Button::Select, PadButton::Select),
+    (gilrs::Button::DPadUp, PadButton::DPadUp),
+    (gilrs::Button::DPadDown, PadButton::DPadDown),
+    (gilrs::Button::DPadLeft, PadButton::DPadLeft),
+    (gilrs::Button::DPadRight, PadButton::DPadRight),
+    (gilrs::Button::LeftTrigger, PadButton::LeftShoulder),
+    (gilrs::Button::RightTrigger, PadButton::RightShoulder),
+];
+
+#[cfg(feature = "gamepad")]
+impl GilrsGamepad {
+    /// `None` if gamepads can't be read on this system.
+    pub fn new() -> Option<Self> {
+        match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(GilrsGamepad {
+                gilrs,
+                active: None,
+            }),
+            Err(e) => {
+                error!("Gamepads disabled: {e}");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "gamepad")]
+impl GamepadSource for GilrsGamepad {
+    fn poll(&mut self) -> Option<GamepadState> {
+        // gilrs only updates its state while events are drained.
+        while let Some(event) = self.gilrs.next_event() {
+            self.active = Some(event.id);
+        }
+        let pad = self
+            .active
+            .and_then(|id| self.gilrs.connected_gamepad(id))
+            .or_else(|| self.gilrs.gamepads().next().map(|(_, el)| el))?;
+        let trigger = |button| pad.button_data(button).map_or(0., |el| el.value());
+        // gilrs sticks point up, ours point down.
+        let stick = |x, y| vec2(pad.value(x), -pad.value(y));
+        Some(GamepadState {
+            left_stick: stick(gilrs::Axis::LeftStickX, gilrs::Axis::LeftStickY),
+            right_stick: stick(gilrs::Axis::RightStickX, gilrs::Axis::RightStickY),
+            left_trigger: trigger(gilrs::Button::LeftTrigger2),
+            right_trigger: trigger(gilrs::Button::RightTrigger2),
+            buttons: GILRS_BUTTONS
+                .into_iter()
+                .filter(|(from, _)| pad.is_pressed(*from))
+                .map(|(_, to)| to)
+                .collect(),
+        })
+    }
+}
+
+/// Replays a fixed sequence of states, one per frame, then holds the last one.
+#[cfg(test)]
+pub struct SyntheticGamepad {
+    frames: VecDeque<GamepadState>,
+    last: Option<GamepadState>,
+}
+
+#[cfg(test)]
+impl SyntheticGamepad {
+    pub fn new(frames: impl IntoIterator<Item = GamepadState>) -> Self {
+        SyntheticGamepad {
+            frames: frames.into_iter().collect(),
+            last: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl GamepadSource for SyntheticGamepad {
+    fn poll(&mut self) -> Option<GamepadState> {
+        if let Some(next) = self.frames.pop_front() {
+            self.last = Some(next);
+        }
+        self.last.clone()
+    }
+}
+
+pub struct GamepadMan {
+    source: Box<dyn GamepadSource>,
+    state: Option<GamepadState>,
+    buttonpress: HashSet<PadButton>,
+    buttons: HashSet<PadButton>,
+    caught: HashSet<PadButton>,
+}
+
+impl GamepadMan {
+    pub fn new(source: Box<dyn GamepadSource>) -> Self {
+        GamepadMan {
+            source,
+            state: None,
+            buttonpress: HashSet::new(),
+            buttons: HashSet::new(),
+            caught: HashSet::new(),
+        }
+    }
+    pub fn update(&mut self) {
+        self.state = self.source.poll();
+        let held = self
+            .state
+            .as_ref()
+            .map(GamepadState::held)
+            .unwrap_or_default();
+        self.buttonpress = held.difference(&self.buttons).copied().collect();
+        self.buttons = held;
+        self.caught.clear();
+    }
+    pub fn connected(&self) -> bool {
+        self.state.is_some()
+    }
+    pub fn buttonpress(&mut self, button: PadButton) -> bool {
+        if self.buttonpress.contains(&button) & !self.caught.contains(&button) {
+            self.caught.insert(button);
+            true
+        } else {
+            false
+        }
+    }
+    pub fn buttondown(&mut self, button: PadButton) -> bool {
+        self.buttons.contains(&button)
+    }
+    /// Some button pressed this frame that nothing has caught yet.
+    pub fn any_buttonpress(&mut self) -> Option<PadButton> {
+        let button = *self.buttonpress.difference(&self.caught).next()?;
+        self.caught.insert(button);
+        Some(button)
+    }
+    /// Left stick with the deadzone applied. Its length is at most 1.
+    pub fn left_stick(&self) -> Vec2 {
+        Self::deadzone(self.state.as_ref().map_or(Vec2::ZERO, |el| el.left_stick))
+    }
+    /// Right stick with the deadzone applied. Its length is at most 1.
+    pub fn right_stick(&self) -> Vec2 {
+        Self::deadzone(self.state.as_ref().map_or(Vec2::ZERO, |el| el.right_stick))
+    }
+    /// Rescales so the stick goes from 0 at the deadzone edge to 1 at full tilt.
+    fn deadzone(stick: Vec2) -> Vec2 {
+        let len = stick.length();
+        if len < DEADZONE {
+            return Vec2::ZERO;
+        }
+        stick / len * ((len.min(1.) - DEADZONE) / (1. - DEADZONE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holding(buttons: &[PadButton]) -> GamepadState {
+        GamepadState {
+            buttons: buttons.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn presses_fire_once_and_are_caught() {
+        let mut pad = GamepadMan::new(Box::new(SyntheticGamepad::new([
+            holding(&[]),
+            holding(&[PadButton::South]),
+            holding(&[PadButton::South]),
+            holding(&[]),
+        ])));
+        pad.update();
+        assert!(pad.connected());
+        assert!(!pad.buttonpress(PadButton::South));
+        pad.update();
+        assert!(pad.buttondown(PadButton::South));
+        assert_eq!(pad.any_buttonpress(), Some(PadButton::South));
+        // already caught this frame.
+        assert!(!pad.buttonpress(PadButton::South));
+        pad.update();
+        assert!(pad.buttondown(PadButton::South));
+        assert!(!pad.buttonpress(PadButton::South));
+        pad.update();
+        assert!(!pad.buttondown(PadButton::South));
+        // the last frame is held from now on.
+        pad.update();
+        assert!(!pad.buttondown(PadButton::South));
+    }
+
+    #[test]
+    fn triggers_become_buttons_past_the_threshold() {
+        let mut pad = GamepadMan::new(Box::new(SyntheticGamepad::new([
+            GamepadState {
+                right_trigger: TRIGGER_THRESHOLD - 0.1,
+                ..Default::default()
+            },
+            GamepadState {
+                right_trigger: TRIGGER_THRESHOLD,
+                ..Default::default()
+            },
+        ])));
+        pad.update();
+        assert!(!pad.buttondown(PadButton::RightTrigger));
+        pad.update();
+        assert!(pad.buttonpress(PadButton::RightTrigger));
+        assert!(!pad.buttondown(PadButton::LeftTrigger));
+    }
+
+    #[test]
+    fn sticks_rescale_past_the_deadzone() {
+        let stick = |left_stick| {
+            let mut pad = GamepadMan::new(Box::new(SyntheticGamepad::new([GamepadState {
+                left_stick,
+                ..Default::default()
+            }])));
+            pad.update();
+            pad.left_stick()
+        };
+        assert_eq!(stick(vec2(DEADZONE * 0.9, 0.)), Vec2::ZERO);
+        assert!(stick(vec2(0., -1.)).distance(vec2(0., -1.)) < 1e-5);
+        // past full tilt is clamped.
+        assert!((stick(vec2(3., 4.)).length() - 1.).abs() < 1e-5);
+        let half = DEADZONE + (1. - DEADZONE) / 2.;
+        assert!((stick(vec2(half, 0.)).x - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn disconnected_pad_reads_as_idle() {
+        let mut pad = GamepadMan::new(Box::new(NullGamepad));
+        pad.update();
+        assert!(!pad.connected());
+        assert_eq!(pad.left_stick(), Vec2::ZERO);
+        assert_eq!(pad.any_buttonpress(), None);
+    }
+}
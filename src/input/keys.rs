@@ -6,6 +6,7 @@ pub struct KbdMan {
     keypress: HashSet<KeyCode>,
     keys: HashSet<KeyCode>,
     caught: HashSet<KeyCode>,
+    chars: Vec<char>,
 }
 
 impl KbdMan {
@@ -14,12 +15,21 @@ impl KbdMan {
             keypress: HashSet::new(),
             keys: HashSet::new(),
             caught: HashSet::new(),
+            chars: Vec::new(),
         }
     }
     pub fn update(&mut self) {
         self.keys = get_keys_down();
         self.keypress = get_keys_pressed();
         self.caught.clear();
+        self.chars.clear();
+        while let Some(c) = get_char_pressed() {
+            self.chars.push(c);
+        }
+    }
+    /// Characters typed this frame, for text entry.
+    pub fn chars(&self) -> &[char] {
+        &self.chars
     }
     pub fn keypress(&mut self, keycode: KeyCode) -> bool {
         if self.keypress.contains(&keycode) & !self.caught.contains(&keycode) {
@@ -46,4 +56,10 @@ impl KbdMan {
     pub fn keydown(&mut self, keycode: KeyCode) -> bool {
         self.keys.contains(&keycode)
     }
+    /// Some key pressed this frame that nothing has caught yet.
+    pub fn any_keypress(&mut self) -> Option<KeyCode> {
+        let key = *self.keypress.difference(&self.caught).next()?;
+        self.caught.insert(key);
+        Some(key)
+    }
 }
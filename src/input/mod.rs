@@ -1,14 +1,149 @@
+use actions::{Action, ActionMap, Binding};
+use gamepad::{GamepadSource, NullGamepad};
+use macroquad::math::{Vec2, vec2};
+
+use crate::{renderer::camera::Camera, util::screen_angle};
+
+pub mod actions;
+pub mod gamepad;
 pub mod keys;
 pub mod mouse;
 
 pub struct InputMan {
     pub kbd: keys::KbdMan,
+    pub mouse: mouse::MouseMan,
+    pub pad: gamepad::GamepadMan,
+    pub actions: ActionMap,
+    /// Where the last aim from `aim` came from.
+    pub aim_source: AimSource,
+}
+
+/// What the player last aimed with. The crosshair follows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AimSource {
+    #[default]
+    Mouse,
+    Pad,
 }
 
 impl InputMan {
-    pub fn new() -> Self {
+    /// Reads gamepads through gilrs with the `gamepad` feature, otherwise keyboard and mouse only.
+    pub fn new(actions: ActionMap) -> Self {
+        #[cfg(feature = "gamepad")]
+        if let Some(pad) = gamepad::GilrsGamepad::new() {
+            return Self::with_gamepad(Box::new(pad), actions);
+        }
+        Self::with_gamepad(Box::new(NullGamepad), actions)
+    }
+    /// Reads gamepad state from `source` instead of the default backend.
+    pub fn with_gamepad(source: Box<dyn GamepadSource>, actions: ActionMap) -> Self {
         InputMan {
             kbd: keys::KbdMan::new(),
+            mouse: mouse::MouseMan::new(),
+            pad: gamepad::GamepadMan::new(source),
+            actions,
+            aim_source: AimSource::Mouse,
+        }
+    }
+    /// Snapshots this frame's input. Call once per frame, before the model updates.
+    pub fn update(&mut self) {
+        self.kbd.update();
+        self.mouse.update();
+        self.pad.update();
+    }
+    /// Whether `action` was triggered this frame. Catches the binding, like `KbdMan::keypress`.
+    pub fn pressed(&mut self, action: Action) -> bool {
+        let mut pressed = false;
+        for binding in self.actions.get(action) {
+            pressed |= match *binding {
+                Binding::Key(key) => self.kbd.keypress(key),
+                Binding::Mouse(button) => self.mouse.buttonpress(button),
+                Binding::Pad(button) => self.pad.buttonpress(button),
+            };
+        }
+        pressed
+    }
+    /// Whether any binding of `action` is held.
+    pub fn down(&mut self, action: Action) -> bool {
+        let mut down = false;
+        for binding in self.actions.get(action) {
+            down |= match *binding {
+                Binding::Key(key) => self.kbd.keydown(key),
+                Binding::Mouse(button) => self.mouse.buttondown(button),
+                Binding::Pad(button) => self.pad.buttondown(button),
+            };
+        }
+        down
+    }
+    /// Any uncaught key or button pressed this frame, for rebinding.
+    pub fn any_press(&mut self) -> Option<Binding> {
+        self.kbd
+            .any_keypress()
+            .map(Binding::Key)
+            .or_else(|| self.mouse.any_buttonpress().map(Binding::Mouse))
+            .or_else(|| self.pad.any_buttonpress().map(Binding::Pad))
+    }
+    /// Requested movement direction, scaled by how far the left stick is tilted.
+    /// Digital movement bindings give components of -1, 0 or 1.
+    pub fn movement(&mut self) -> Vec2 {
+        let stick = self.pad.left_stick();
+        if stick != Vec2::ZERO {
+            return stick;
+        }
+        let axis = |neg: bool, pos: bool| pos as i32 as f32 - neg as i32 as f32;
+        vec2(
+            axis(self.down(Action::MoveLeft), self.down(Action::MoveRight)),
+            axis(self.down(Action::MoveUp), self.down(Action::MoveDown)),
+        )
+    }
+    /// Aim angle from world point `origin`, as `util::screen_angle`.
+    /// `None` means keep the previous aim: a gamepad is connected and neither it nor the mouse aimed.
+    pub fn aim(&mut self, camera: &Camera, origin: Vec2) -> Option<f32> {
+        let stick = self.pad.right_stick();
+        if stick != Vec2::ZERO {
+            self.aim_source = AimSource::Pad;
+            Some(screen_angle(stick))
+        } else if self.mouse.moved() || !self.pad.connected() {
+            self.aim_source = AimSource::Mouse;
+            Some(self.mouse.angle_from(camera, origin))
+        } else {
+            None
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gamepad::{GamepadState, SyntheticGamepad};
+
+    fn stick(right: Vec2) -> GamepadState {
+        GamepadState {
+            right_stick: right,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn aim_source_follows_whatever_aimed_last() {
+        let pad = SyntheticGamepad::new([stick(vec2(1., 0.)), stick(Vec2::ZERO)]);
+        let mut input = InputMan::with_gamepad(Box::new(pad), ActionMap::default());
+        let camera = Camera::new();
+        let none = std::collections::HashSet::new;
+
+        input.pad.update();
+        assert_eq!(input.aim(&camera, Vec2::ZERO), Some(0.));
+        assert_eq!(input.aim_source, AimSource::Pad);
+
+        // a centered stick with a still mouse keeps both the aim and its source.
+        input.pad.update();
+        assert_eq!(input.aim(&camera, Vec2::ZERO), None);
+        assert_eq!(input.aim_source, AimSource::Pad);
+
+        input
+            .mouse
+            .update_from(none(), none(), none(), vec2(10., 10.), Vec2::ZERO);
+        assert!(input.aim(&camera, Vec2::ZERO).is_some());
+        assert_eq!(input.aim_source, AimSource::Mouse);
+    }
+}
@@ -1 +1,147 @@
-pub struct MouseMan {}
+use std::collections::HashSet;
+
+use macroquad::prelude::*;
+
+use crate::{renderer::camera::Camera, util::screen_angle};
+
+const BUTTONS: [MouseButton; 3] = [MouseButton::Left, MouseButton::Middle, MouseButton::Right];
+
+pub struct MouseMan {
+    buttonpress: HashSet<MouseButton>,
+    buttons: HashSet<MouseButton>,
+    buttonrelease: HashSet<MouseButton>,
+    caught: HashSet<MouseButton>,
+    pos: Vec2,
+    moved: bool,
+    wheel: Vec2,
+}
+
+impl MouseMan {
+    pub fn new() -> Self {
+        MouseMan {
+            buttonpress: HashSet::new(),
+            buttons: HashSet::new(),
+            buttonrelease: HashSet::new(),
+            caught: HashSet::new(),
+            pos: Vec2::ZERO,
+            moved: false,
+            wheel: Vec2::ZERO,
+        }
+    }
+    pub fn update(&mut self) {
+        let filter = |f: fn(MouseButton) -> bool| BUTTONS.into_iter().filter(|el| f(*el)).collect();
+        self.update_from(
+            filter(is_mouse_button_down),
+            filter(is_mouse_button_pressed),
+            filter(is_mouse_button_released),
+            mouse_position().into(),
+            mouse_wheel().into(),
+        );
+    }
+    /// `update` with this frame's state given directly instead of read from macroquad.
+    pub fn update_from(
+        &mut self,
+        down: HashSet<MouseButton>,
+        pressed: HashSet<MouseButton>,
+        released: HashSet<MouseButton>,
+        pos: Vec2,
+        wheel: Vec2,
+    ) {
+        self.buttons = down;
+        self.buttonpress = pressed;
+        self.buttonrelease = released;
+        self.moved = pos != self.pos;
+        self.pos = pos;
+        self.wheel = wheel;
+        self.caught.clear();
+    }
+    pub fn buttonpress(&mut self, button: MouseButton) -> bool {
+        if self.buttonpress.contains(&button) & !self.caught.contains(&button) {
+            self.caught.insert(button);
+            true
+        } else {
+            false
+        }
+    }
+    pub fn buttondown(&mut self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+    pub fn buttonrelease(&mut self, button: MouseButton) -> bool {
+        self.buttonrelease.contains(&button)
+    }
+    /// Some button pressed this frame that nothing has caught yet.
+    pub fn any_buttonpress(&mut self) -> Option<MouseButton> {
+        let button = *self.buttonpress.difference(&self.caught).next()?;
+        self.caught.insert(button);
+        Some(button)
+    }
+    /// Cursor position in screen pixels.
+    pub fn pos(&self) -> Vec2 {
+        self.pos
+    }
+    /// Whether the cursor moved since last frame.
+    pub fn moved(&self) -> bool {
+        self.moved
+    }
+    /// Wheel movement this frame. Positive y scrolls up.
+    pub fn wheel(&self) -> Vec2 {
+        self.wheel
+    }
+    /// Cursor position in world coordinates.
+    pub fn world_pos(&self, camera: &Camera) -> Vec2 {
+        camera.screen_to_world(self.pos)
+    }
+    /// Aim direction from world point `origin` towards the cursor, as `util::screen_angle`.
+    pub fn angle_from(&self, camera: &Camera, origin: Vec2) -> f32 {
+        screen_angle(self.world_pos(camera) - origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(buttons: &[MouseButton]) -> HashSet<MouseButton> {
+        buttons.iter().copied().collect()
+    }
+
+    #[test]
+    fn release_follows_press_and_hold() {
+        let left = MouseButton::Left;
+        let mut mouse = MouseMan::new();
+        mouse.update_from(set(&[left]), set(&[left]), set(&[]), Vec2::ZERO, Vec2::ZERO);
+        assert!(mouse.buttonpress(left));
+        assert!(mouse.buttondown(left));
+        assert!(!mouse.buttonrelease(left));
+
+        mouse.update_from(set(&[left]), set(&[]), set(&[]), Vec2::ZERO, Vec2::ZERO);
+        assert!(!mouse.buttonpress(left));
+        assert!(mouse.buttondown(left));
+        assert!(!mouse.buttonrelease(left));
+
+        mouse.update_from(set(&[]), set(&[]), set(&[left]), Vec2::ZERO, Vec2::ZERO);
+        assert!(!mouse.buttondown(left));
+        assert!(mouse.buttonrelease(left));
+        assert!(!mouse.buttonrelease(MouseButton::Right));
+
+        mouse.update_from(set(&[]), set(&[]), set(&[]), Vec2::ZERO, Vec2::ZERO);
+        assert!(!mouse.buttonrelease(left));
+    }
+
+    #[test]
+    fn catching_a_press_does_not_hide_its_release() {
+        let right = MouseButton::Right;
+        let mut mouse = MouseMan::new();
+        mouse.update_from(
+            set(&[]),
+            set(&[right]),
+            set(&[right]),
+            vec2(3., 4.),
+            Vec2::ZERO,
+        );
+        assert_eq!(mouse.any_buttonpress(), Some(right));
+        assert!(!mouse.buttonpress(right));
+        assert!(mouse.buttonrelease(right));
+        assert!(mouse.moved());
+    }
+}
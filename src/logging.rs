@@ -0,0 +1,284 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{LineWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::Instant,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    errors::{GameError, Nresult},
+    util::{format_unix_time, unix_now},
+};
+
+/// Lives next to `model::SAVE_LOC`.
+pub const LOG_LOC: &str = "logs/";
+const LOG_NAME: &str = "game";
+/// Size at which the current log is rotated out, in bytes.
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+/// Rotated logs kept besides the current one, `game.1.log` being the newest.
+const KEPT_LOGS: u32 = 4;
+/// Lines kept in memory for crash reports.
+pub const RECENT_LINES: usize = 50;
+/// Filter directives, e.g. `warn,pixel_rebels::model=debug`.
+pub const LOG_ENV: &str = "PIXEL_REBELS_LOG";
+
+static LOGGER: OnceCell<Logger> = OnceCell::new();
+
+/// Level per module target. The longest matching prefix wins, otherwise `default` applies.
+struct Filter {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    /// Parses comma separated `level` and `target=level` directives.
+    fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Filter::default();
+        for directive in spec.split(',').map(str::trim).filter(|el| !el.is_empty()) {
+            match directive.split_once('=') {
+                Some((target, level)) => {
+                    let level = level
+                        .parse()
+                        .map_err(|_| format!("bad level in `{directive}`"))?;
+                    filter.targets.push((target.to_owned(), level));
+                }
+                None => {
+                    filter.default = directive
+                        .parse()
+                        .map_err(|_| format!("bad level `{directive}`"))?;
+                }
+            }
+        }
+        // longest first, so the first match is the most specific one.
+        filter
+            .targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Ok(filter)
+    }
+    fn level(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .map_or(self.default, |(_, level)| *level)
+    }
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            default: if cfg!(debug_assertions) {
+                LevelFilter::Debug
+            } else {
+                LevelFilter::Info
+            },
+            targets: Vec::new(),
+        }
+    }
+}
+
+struct LogFile {
+    /// `None` if the log file couldn't be opened. Logging to stderr still works.
+    writer: Option<LineWriter<File>>,
+    written: u64,
+}
+
+/// Writes every record to stderr and `LOG_LOC/game.log`, and remembers the last few lines.
+struct Logger {
+    filter: Filter,
+    start: Instant,
+    file: Mutex<LogFile>,
+    recent: Mutex<VecDeque<String>>,
+}
+
+/// Locks even if a panic happened while the lock was held. Logging must keep working then.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "[{:>9.3}] {:<5} {}: {}",
+            self.start.elapsed().as_secs_f32(),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        eprintln!("{line}");
+        {
+            let mut recent = lock(&self.recent);
+            if recent.len() == RECENT_LINES {
+                recent.pop_front();
+            }
+            recent.push_back(line.clone());
+        }
+        let mut file = lock(&self.file);
+        if file.written > MAX_LOG_SIZE {
+            file.writer = None;
+            rotate();
+            *file = open_log();
+        }
+        if let Some(writer) = &mut file.writer
+            && writeln!(writer, "{line}").is_ok()
+        {
+            file.written += line.len() as u64 + 1;
+        }
+    }
+    /// Skips flushing if another thread is writing, so a panic hook can't deadlock here.
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.try_lock()
+            && let Some(writer) = &mut file.writer
+        {
+            let _ = writer.flush();
+        }
+    }
+}
+
+fn log_path(index: u32) -> PathBuf {
+    let name = match index {
+        0 => format!("{LOG_NAME}.log"),
+        i => format!("{LOG_NAME}.{i}.log"),
+    };
+    Path::new(LOG_LOC).join(name)
+}
+
+/// Shifts `game.log` to `game.1.log` and so on, dropping the oldest.
+fn rotate() {
+    let _ = fs::remove_file(log_path(KEPT_LOGS));
+    for i in (0..KEPT_LOGS).rev() {
+        let _ = fs::rename(log_path(i), log_path(i + 1));
+    }
+}
+
+fn open_log() -> LogFile {
+    let writer = fs::create_dir_all(LOG_LOC)
+        .and_then(|_| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_path(0))
+        })
+        .ok()
+        .map(LineWriter::new);
+    LogFile { writer, written: 0 }
+}
+
+/// Installs the logger. Every session starts a fresh `game.log`; the previous ones are rotated.
+/// A bad `LOG_ENV` falls back to the default levels and is reported once logging works.
+pub fn init() -> Nresult {
+    let (filter, problem) = match std::env::var(LOG_ENV) {
+        Ok(spec) => match Filter::parse(&spec) {
+            Ok(filter) => (filter, None),
+            Err(e) => (Filter::default(), Some(e)),
+        },
+        Err(_) => (Filter::default(), None),
+    };
+    rotate();
+    let mut file = open_log();
+    let secs = unix_now().as_secs();
+    if let Some(writer) = &mut file.writer {
+        let _ = writeln!(
+            writer,
+            "{} {} started {} UTC",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            format_unix_time(secs)
+        );
+    }
+    let max_level = filter.max_level();
+    let logger = LOGGER.get_or_init(|| Logger {
+        filter,
+        start: Instant::now(),
+        file: Mutex::new(file),
+        recent: Mutex::new(VecDeque::with_capacity(RECENT_LINES)),
+    });
+    log::set_logger(logger)
+        .map_err(|_| GameError::Misc("Tried to initialize logging twice.".into()))?;
+    log::set_max_level(max_level);
+    if let Some(e) = problem {
+        log::warn!("Ignoring {LOG_ENV}: {e}");
+    }
+    Ok(())
+}
+
+/// The last `RECENT_LINES` log lines, oldest first. Empty before `init`.
+/// Doesn't wait for the lock, so it's safe to call from the panic hook.
+pub fn recent() -> Vec<String> {
+    LOGGER
+        .get()
+        .and_then(|el| el.recent.try_lock().ok())
+        .map(|el| el.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+pub fn flush() {
+    log::logger().flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_specs_give_the_default() {
+        for spec in ["", " ", ",", " , ,"] {
+            let filter = Filter::parse(spec).unwrap();
+            assert_eq!(filter.default, Filter::default().default, "{spec:?}");
+            assert!(filter.targets.is_empty(), "{spec:?}");
+        }
+    }
+
+    #[test]
+    fn bad_levels_are_errors() {
+        for spec in ["loud", "warn,loud", "pixel_rebels=loud", "pixel_rebels="] {
+            assert!(Filter::parse(spec).is_err(), "{spec:?}");
+        }
+    }
+
+    #[test]
+    fn the_longest_matching_prefix_wins() {
+        let filter =
+            Filter::parse("warn, pixel_rebels=info, pixel_rebels::model::enemies=trace").unwrap();
+        assert_eq!(filter.level("macroquad"), LevelFilter::Warn);
+        assert_eq!(filter.level("pixel_rebels::audio"), LevelFilter::Info);
+        assert_eq!(
+            filter.level("pixel_rebels::model::enemies::enemymap"),
+            LevelFilter::Trace
+        );
+        // order in the spec doesn't matter.
+        let reversed =
+            Filter::parse("pixel_rebels::model::enemies=trace,pixel_rebels=info,warn").unwrap();
+        assert_eq!(
+            reversed.level("pixel_rebels::model::enemies"),
+            LevelFilter::Trace
+        );
+        assert_eq!(reversed.level("pixel_rebels::model"), LevelFilter::Info);
+    }
+
+    #[test]
+    fn levels_are_case_insensitive_and_later_defaults_win() {
+        let filter = Filter::parse("INFO,Error").unwrap();
+        assert_eq!(filter.default, LevelFilter::Error);
+        assert_eq!(filter.max_level(), LevelFilter::Error);
+        let filter = Filter::parse("off,pixel_rebels=debug").unwrap();
+        assert_eq!(filter.level("other"), LevelFilter::Off);
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+    }
+}
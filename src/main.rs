@@ -1,50 +1,92 @@
 #![deny(clippy::all)]
 
-use errors::{GameError, Nresult};
+use errors::Nresult;
 use macroquad::prelude::*;
-use util::{DEBUG_TX, create_mpsc, set_hooks};
+use util::set_hooks;
 
 mod assets;
+mod audio;
+mod crash;
 mod errors;
 mod input;
+mod logging;
 mod model;
+mod profiler;
 mod renderer;
+mod settings;
 mod util;
 
-#[macroquad::main("PIXEL_REBELS")]
+fn window_conf() -> Conf {
+    settings::Settings::load_or_default().0.window_conf()
+}
+
+#[macroquad::main(window_conf)]
 async fn main() -> Nresult {
-    println!("Starting.");
-    print!("Setting hooks...");
+    if let Err(e) = logging::init() {
+        eprintln!("Failed to initialize logging: {e}");
+    }
+    info!("Starting.");
     set_hooks();
-    println!(" OK");
-    print!("Starting asset loads...");
-    assets::init_all().expect("Asset load failed.");
-    println!(" OK");
-    print!("initializing model...");
-    let mut model = model::GameModel::new();
+    info!("Loading assets...");
+    let (assets, problems) = assets::init_all().await;
+    if !problems.is_empty() {
+        warn!("{} asset problem(s), using placeholders", problems.len());
+        for e in problems {
+            error!("{}", e);
+        }
+    }
+    info!("Loading settings...");
+    let (settings, problem) = settings::Settings::load_or_default();
+    if let Some(e) = problem {
+        error!("{}, using default settings", e);
+    }
+    info!("Loading sounds...");
+    // without the `audio` feature macroquad can't play anything, so don't load it either.
+    let audio: Box<dyn audio::AudioBackend> = if cfg!(feature = "audio") {
+        Box::new(audio::MacroquadAudio::load().await)
+    } else {
+        Box::new(audio::NullAudio)
+    };
+    info!("Initializing model...");
+    let input = input::InputMan::new(input::actions::ActionMap::load_or_default());
+    let mut model = model::GameModel::new(
+        assets,
+        settings,
+        audio,
+        input,
+        Box::new(util::MacroquadClock),
+    );
     model.init()?;
-    println!(" OK");
-    print!("initializing debug systems...");
-    let mut rx = create_mpsc().await?;
-    let tx = util::DEBUG_TX.get().unwrap().clone();
-    model
-        .set_debug_tx(tx)
-        .expect("Errored non non-erroring operation(???)");
-    println!(" OK");
-    miniquad::window::show_mouse(false);
-    println!("ALL CLEAR");
+    // `window_conf` already opened the window in this mode, with the cursor shown.
+    let mut window_mode = model.settings.window_mode;
+    let mut cursor_visible = true;
+    info!("ALL CLEAR");
     loop {
-        if util::INTERRUPT.load(std::sync::atomic::Ordering::Relaxed) {
-            continue;
+        if let Some(signal) = util::shutdown_signal() {
+            info!("Received signal {signal}, shutting down.");
+            model.shutdown();
+            logging::flush();
+            // exiting closes the window. 128 + signal is what shells expect from a killed process.
+            std::process::exit(128 + signal);
+        }
+        if util::INTERRUPT.load(std::sync::atomic::Ordering::Acquire) {
+            // a panic on another thread. The panic hook already reported it and saved the run.
+            logging::flush();
+            std::process::exit(101);
         }
-        let _ = DEBUG_TX
-            .get()
-            .unwrap()
-            .unbounded_send(format!("FPS: {}", get_fps()));
-        model.input.kbd.update();
+        model.input.update();
+        model.renderer.camera.screen = vec2(screen_width(), screen_height());
         model.update();
+        if model.settings.window_mode != window_mode {
+            window_mode = model.settings.window_mode;
+            set_fullscreen(window_mode == settings::WindowMode::Fullscreen);
+        }
+        if model.cursor_visible() != cursor_visible {
+            cursor_visible = model.cursor_visible();
+            show_mouse(cursor_visible);
+        }
         model.call_render().await;
-        model.call_render_dbg(&mut rx)?;
+        profiler::end_frame();
         next_frame().await;
     }
 }
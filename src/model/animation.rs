@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+
+/// Named animation states shared by every animated entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnimState {
+    Idle,
+    Walk,
+    Attack,
+    Hurt,
+    Death,
+}
+
+impl AnimState {
+    pub const ALL: [AnimState; 5] = [
+        Self::Idle,
+        Self::Walk,
+        Self::Attack,
+        Self::Hurt,
+        Self::Death,
+    ];
+
+    /// Parses the clip name used in asset file names, e.g. `walk` in `walk3.png`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "idle" => Some(Self::Idle),
+            "walk" => Some(Self::Walk),
+            "attack" => Some(Self::Attack),
+            "hurt" => Some(Self::Hurt),
+            "death" => Some(Self::Death),
+            _ => None,
+        }
+    }
+    /// How a clip for this state plays by default.
+    pub fn default_mode(self) -> PlayMode {
+        match self {
+            Self::Idle | Self::Walk => PlayMode::Loop,
+            Self::Attack | Self::Hurt | Self::Death => PlayMode::Once,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    Loop,
+    /// Plays once and holds the last frame.
+    Once,
+}
+
+/// Something the game may react to while a clip plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimEvent {
+    /// Fired when a frame tagged with `Footstep` is entered.
+    Footstep,
+    /// Fired once when a `PlayMode::Once` clip reaches its end.
+    Finished,
+}
+
+/// A sequence of frames. `T` is whatever the renderer needs to draw a frame.
+pub struct Clip<T> {
+    /// Duration in seconds and data of each frame.
+    pub frames: Vec<(f32, T)>,
+    pub mode: PlayMode,
+    /// Events fired when the given frame index is entered.
+    pub events: Vec<(usize, AnimEvent)>,
+}
+
+impl<T> Clip<T> {
+    pub fn new(frames: Vec<(f32, T)>, mode: PlayMode) -> Self {
+        Clip {
+            frames,
+            mode,
+            events: Vec::new(),
+        }
+    }
+    pub fn with_event(mut self, frame: usize, event: AnimEvent) -> Self {
+        self.events.push((frame, event));
+        self
+    }
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|(d, _)| d).sum()
+    }
+    /// Index of the frame showing at `time` seconds into the clip.
+    pub fn frame_index(&self, time: f32) -> usize {
+        let mut acc = 0.;
+        for (i, (duration, _)) in self.frames.iter().enumerate() {
+            acc += duration;
+            if time < acc {
+                return i;
+            }
+        }
+        self.frames.len().saturating_sub(1)
+    }
+}
+
+/// Clips of one entity kind, keyed by state. Missing states fall back to `Idle`.
+pub struct Clips<T> {
+    clips: HashMap<AnimState, Clip<T>>,
+}
+
+impl<T> Clips<T> {
+    pub fn new() -> Self {
+        Clips {
+            clips: HashMap::new(),
+        }
+    }
+    pub fn insert(&mut self, state: AnimState, clip: Clip<T>) {
+        self.clips.insert(state, clip);
+    }
+    pub fn has(&self, state: AnimState) -> bool {
+        self.clips.contains_key(&state)
+    }
+    pub fn get(&self, state: AnimState) -> Option<&Clip<T>> {
+        self.clips
+            .get(&state)
+            .or_else(|| self.clips.get(&AnimState::Idle))
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&AnimState, &Clip<T>)> {
+        self.clips.iter()
+    }
+}
+
+impl<T> Default for Clips<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Playback position of one entity. Holds no frame data, so it is cheap to keep per enemy.
+#[derive(Debug, Clone, Copy)]
+pub struct Animator {
+    pub state: AnimState,
+    /// Seconds into the current clip.
+    pub time: f32,
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Animator {
+            state: AnimState::Idle,
+            time: 0.,
+        }
+    }
+    /// Switches clips, restarting playback only if the state actually changed.
+    pub fn set_state(&mut self, state: AnimState) {
+        if self.state != state {
+            self.state = state;
+            self.time = 0.;
+        }
+    }
+    /// Whether a `Once` clip has played through. States without their own clip count as done.
+    pub fn finished<T>(&self, clips: &Clips<T>) -> bool {
+        if !clips.has(self.state) {
+            return true;
+        }
+        clips
+            .get(self.state)
+            .is_some_and(|clip| clip.mode == PlayMode::Once && self.time >= clip.duration())
+    }
+    /// Advances playback by `dt` and returns the events of every frame entered on the way.
+    pub fn update<T>(&mut self, clips: &Clips<T>, dt: f32) -> Vec<AnimEvent> {
+        let mut events = Vec::new();
+        let Some(clip) = clips.get(self.state) else {
+            return events;
+        };
+        let duration = clip.duration();
+        let count = clip.frames.len();
+        if count == 0 || duration <= 0. {
+            return events;
+        }
+        let old_time = self.time;
+        let old_frame = clip.frame_index(old_time);
+        let new_time = old_time + dt;
+        let steps = match clip.mode {
+            PlayMode::Loop => {
+                self.time = new_time % duration;
+                let cycles = (new_time / duration) as usize;
+                (cycles * count + clip.frame_index(self.time)).saturating_sub(old_frame)
+            }
+            PlayMode::Once => {
+                self.time = new_time.min(duration);
+                clip.frame_index(self.time) - old_frame
+            }
+        };
+        for step in 1..=steps.min(count) {
+            let frame = (old_frame + step) % count;
+            events.extend(
+                clip.events
+                    .iter()
+                    .filter(|(i, _)| *i == frame)
+                    .map(|(_, event)| *event),
+            );
+        }
+        if clip.mode == PlayMode::Once && old_time < duration && self.time >= duration {
+            events.push(AnimEvent::Finished);
+        }
+        events
+    }
+    /// Frame data to draw right now.
+    pub fn frame<'a, T>(&self, clips: &'a Clips<T>) -> Option<&'a T> {
+        let clip = clips.get(self.state)?;
+        clip.frames
+            .get(clip.frame_index(self.time))
+            .map(|(_, frame)| frame)
+    }
+}
+
+impl Default for Animator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 1e-5;
+
+    /// Frames lasting 0.1, 0.2 and 0.3 seconds, with a footstep on the second one.
+    fn clips(mode: PlayMode) -> Clips<u32> {
+        let mut clips = Clips::new();
+        clips.insert(
+            AnimState::Idle,
+            Clip::new(vec![(0.1, 0), (0.2, 1), (0.3, 2)], mode).with_event(1, AnimEvent::Footstep),
+        );
+        clips
+    }
+
+    #[test]
+    fn frames_follow_their_own_durations() {
+        let clips = clips(PlayMode::Loop);
+        let mut anim = Animator::new();
+        let mut shown = Vec::new();
+        for _ in 0..6 {
+            shown.push(*anim.frame(&clips).unwrap());
+            anim.update(&clips, 0.1);
+        }
+        assert_eq!(shown, [0, 1, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn loops_wrap_and_fire_events_every_cycle() {
+        let clips = clips(PlayMode::Loop);
+        let mut anim = Animator::new();
+        assert_eq!(anim.update(&clips, 0.15), [AnimEvent::Footstep]);
+        assert!(anim.update(&clips, 0.2).is_empty());
+        // past the end: back to frame 0, then into frame 1 again.
+        assert!(anim.update(&clips, 0.3).is_empty());
+        assert!((anim.time - 0.05).abs() < EPS);
+        assert_eq!(*anim.frame(&clips).unwrap(), 0);
+        // several whole cycles at once fire at most one footstep per frame passed.
+        assert_eq!(anim.update(&clips, 1.2), [AnimEvent::Footstep]);
+        assert!((anim.time - 0.05).abs() < EPS);
+        assert!(!anim.finished(&clips));
+    }
+
+    #[test]
+    fn once_holds_the_last_frame_and_finishes_once() {
+        let clips = clips(PlayMode::Once);
+        let mut anim = Animator::new();
+        assert_eq!(anim.update(&clips, 0.5), [AnimEvent::Footstep]);
+        assert!(!anim.finished(&clips));
+        assert_eq!(anim.update(&clips, 0.5), [AnimEvent::Finished]);
+        assert!(anim.finished(&clips));
+        assert!(anim.update(&clips, 0.5).is_empty());
+        assert_eq!(*anim.frame(&clips).unwrap(), 2);
+        // switching state restarts playback; the same state doesn't.
+        anim.set_state(AnimState::Idle);
+        assert!(anim.finished(&clips));
+        anim.set_state(AnimState::Walk);
+        assert_eq!(anim.time, 0.);
+    }
+
+    #[test]
+    fn missing_states_fall_back_to_idle() {
+        let clips = clips(PlayMode::Loop);
+        let mut anim = Animator::new();
+        anim.set_state(AnimState::Attack);
+        assert_eq!(*anim.frame(&clips).unwrap(), 0);
+        // nothing to play through, so it counts as done.
+        assert!(anim.finished(&clips));
+        assert!(Animator::new().update(&Clips::<u32>::new(), 1.).is_empty());
+    }
+}
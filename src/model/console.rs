@@ -0,0 +1,487 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    str::FromStr,
+};
+
+use macroquad::prelude::*;
+
+use super::{GameModel, weapons::Weapon};
+use crate::errors::{GameError, Result};
+
+/// Lines of output kept for scrolling back.
+pub const OUTPUT_LINES: usize = 200;
+/// Minimum distance from the player for enemies spawned without a location.
+const SPAWN_MIN_DIST: f32 = 1000.;
+/// Most enemies one `spawn` may add.
+const MAX_SPAWN_COUNT: u32 = 5000;
+const MAX_TIME_SCALE: f32 = 10.;
+
+/// Runs a command with its arguments, not including the name. `Ok` is printed as is.
+pub type CommandFn = fn(&mut GameModel, &[&str]) -> Result<String>;
+/// Candidates for argument `index` of a command, before filtering by what was typed.
+pub type CompleteFn = fn(&GameModel, usize) -> Vec<String>;
+
+#[derive(Clone, Copy)]
+pub struct Command {
+    pub name: &'static str,
+    /// Arguments, as shown by `help`, e.g. `<x> <y>`.
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub run: CommandFn,
+    pub complete: Option<CompleteFn>,
+}
+
+/// Every command the console knows, by name. Register more with `Commands::register`.
+#[derive(Default)]
+pub struct Commands {
+    map: BTreeMap<&'static str, Command>,
+}
+
+impl Commands {
+    /// Adds `command`, replacing any command of the same name.
+    pub fn register(&mut self, command: Command) {
+        self.map.insert(command.name, command);
+    }
+    pub fn get(&self, name: &str) -> Option<Command> {
+        self.map.get(name).copied()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Command> {
+        self.map.values()
+    }
+}
+
+/// The drop-down developer console. Gameplay is frozen while it is open.
+pub struct Console {
+    pub open: bool,
+    /// Line being typed.
+    pub input: String,
+    /// Printed lines, oldest first.
+    output: VecDeque<String>,
+    /// Lines that were run, oldest first.
+    history: Vec<String>,
+    /// Index into `history` while browsing it with the arrow keys.
+    history_pos: Option<usize>,
+    pub commands: Commands,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        let mut commands = Commands::default();
+        for command in builtin_commands() {
+            commands.register(command);
+        }
+        Console {
+            open: false,
+            input: String::new(),
+            output: VecDeque::with_capacity(OUTPUT_LINES),
+            history: Vec::new(),
+            history_pos: None,
+            commands,
+        }
+    }
+    /// Prints `text`, one output line per line of text.
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            if self.output.len() == OUTPUT_LINES {
+                self.output.pop_front();
+            }
+            self.output.push_back(line.to_owned());
+        }
+    }
+    pub fn output(&self) -> &VecDeque<String> {
+        &self.output
+    }
+    /// Moves through previously run lines. `back` goes to older ones.
+    pub fn browse_history(&mut self, back: bool) {
+        let pos = match (self.history_pos, back) {
+            (None, true) => self.history.len().checked_sub(1),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) => (i + 1 < self.history.len()).then_some(i + 1),
+        };
+        self.history_pos = pos;
+        self.input = pos.map_or_else(String::new, |i| self.history[i].clone());
+    }
+    /// Takes the typed line, remembering it in the history.
+    fn take_input(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        self.history_pos = None;
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        line
+    }
+    /// Completes the last word of the input from `candidates`. A single match is filled in,
+    /// several are printed and the input is extended to what they have in common.
+    fn complete_with(&mut self, candidates: Vec<String>) {
+        let word_start = self.input.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &self.input[word_start..];
+        let matches: Vec<String> = candidates
+            .into_iter()
+            .filter(|el| el.starts_with(word))
+            .collect();
+        match matches.as_slice() {
+            [] => {}
+            [only] => {
+                self.input.replace_range(word_start.., only);
+                self.input.push(' ');
+            }
+            [first, rest @ ..] => {
+                let common = rest.iter().fold(first.as_str(), |acc, el| {
+                    let len = acc
+                        .char_indices()
+                        .zip(el.chars())
+                        .take_while(|((_, a), b)| a == b)
+                        .last()
+                        .map_or(0, |((i, a), _)| i + a.len_utf8());
+                    &acc[..len]
+                });
+                let common = common.to_owned();
+                self.print(&matches.join("  "));
+                self.input.replace_range(word_start.., &common);
+            }
+        }
+    }
+}
+
+impl GameModel {
+    /// Runs `line` as a console command and prints the result.
+    pub fn run_command(&mut self, line: &str) {
+        self.console.print(&format!("> {line}"));
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return;
+        };
+        let args: Vec<&str> = words.collect();
+        let result = match self.console.commands.get(name) {
+            Some(command) => (command.run)(self, &args),
+            None => Err(GameError::InvalidCommand(format!(
+                "unknown command `{name}`, try `help`"
+            ))),
+        };
+        match result {
+            Ok(msg) => self.console.print(&msg),
+            Err(e) => self.console.print(&e.to_string()),
+        }
+    }
+    /// Runs the typed line.
+    pub fn submit_command(&mut self) {
+        let line = self.console.take_input();
+        self.run_command(&line);
+    }
+    /// Tab completion of command names, then of their arguments.
+    pub fn complete_command(&mut self) {
+        let words: Vec<&str> = self.console.input.split_whitespace().collect();
+        let new_word = self.console.input.is_empty() || self.console.input.ends_with(' ');
+        // index of the word being completed, 0 being the command name.
+        let index = words.len() - usize::from(!new_word);
+        let candidates = if index == 0 {
+            self.console
+                .commands
+                .iter()
+                .map(|el| el.name.to_owned())
+                .collect()
+        } else {
+            match self
+                .console
+                .commands
+                .get(words[0])
+                .and_then(|el| el.complete)
+            {
+                Some(complete) => complete(self, index - 1),
+                None => Vec::new(),
+            }
+        };
+        self.console.complete_with(candidates);
+    }
+}
+
+/// Parses argument `index`, or errors with the command's usage.
+fn arg<T: FromStr>(args: &[&str], index: usize, usage: &str) -> Result<T> {
+    args.get(index)
+        .and_then(|el| el.parse().ok())
+        .ok_or_else(|| GameError::InvalidCommand(format!("usage: {usage}")))
+}
+
+fn builtin_commands() -> [Command; 8] {
+    [
+        Command {
+            name: "help",
+            usage: "",
+            help: "lists every command",
+            run: cmd_help,
+            complete: None,
+        },
+        Command {
+            name: "spawn",
+            usage: "<enemy> <count> [x y]",
+            help: "spawns enemies by id, away from the player or at x y",
+            run: cmd_spawn,
+            complete: Some(complete_spawn),
+        },
+        Command {
+            name: "give",
+            usage: "<weapon>",
+            help: "swaps the player's weapon for weapon id",
+            run: cmd_give,
+            complete: Some(complete_give),
+        },
+        Command {
+            name: "god",
+            usage: "",
+            help: "toggles ignoring all damage",
+            run: cmd_god,
+            complete: None,
+        },
+        Command {
+            name: "setmap",
+            usage: "<id>",
+            help: "moves to another map, keeping the run",
+            run: cmd_setmap,
+            complete: Some(complete_setmap),
+        },
+        Command {
+            name: "tp",
+            usage: "<x> <y>",
+            help: "teleports the player, staying on the map",
+            run: cmd_tp,
+            complete: None,
+        },
+        Command {
+            name: "kill_all",
+            usage: "",
+            help: "kills every enemy",
+            run: cmd_kill_all,
+            complete: None,
+        },
+        Command {
+            name: "timescale",
+            usage: "[scale]",
+            help: "shows or sets how fast gameplay runs, 1 being normal",
+            run: cmd_timescale,
+            complete: None,
+        },
+    ]
+}
+
+fn ids(count: usize) -> Vec<String> {
+    (0..count).map(|el| el.to_string()).collect()
+}
+
+fn cmd_help(model: &mut GameModel, _args: &[&str]) -> Result<String> {
+    Ok(model
+        .console
+        .commands
+        .iter()
+        .map(|el| format!("{} {} - {}", el.name, el.usage, el.help).replace("  -", " -"))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn cmd_spawn(model: &mut GameModel, args: &[&str]) -> Result<String> {
+    const USAGE: &str = "spawn <enemy> <count> [x y]";
+    let id: u32 = arg(args, 0, USAGE)?;
+    let count: u32 = arg(args, 1, USAGE)?;
+    if count > MAX_SPAWN_COUNT {
+        return Err(GameError::InvalidCommand(format!(
+            "can spawn at most {MAX_SPAWN_COUNT} at once"
+        )));
+    }
+    if model.assets.enemies.get(id).is_none() {
+        return Err(GameError::InvalidCommand(format!("no enemy with id {id}")));
+    }
+    let at = match args.len() {
+        2 => None,
+        4 => Some(vec2(arg(args, 2, USAGE)?, arg(args, 3, USAGE)?)),
+        _ => return Err(GameError::InvalidCommand(format!("usage: {USAGE}"))),
+    };
+    let map_size = model.world.map_size(&model.assets);
+    for _ in 0..count {
+        match at {
+            Some(loc) => model.world.horde.append(id, loc, &model.assets.enemies),
+            None => model.world.horde.spawn_around(
+                model.world.player_pos,
+                map_size,
+                SPAWN_MIN_DIST,
+                id,
+                &model.assets.enemies,
+            ),
+        }
+    }
+    // gameplay may be frozen by the console, and rendering needs the horde sorted.
+    model.world.horde.sort();
+    Ok(format!("spawned {count} of enemy {id}"))
+}
+
+fn complete_spawn(model: &GameModel, index: usize) -> Vec<String> {
+    match index {
+        0 => ids(model.assets.enemies.len()),
+        _ => Vec::new(),
+    }
+}
+
+fn cmd_give(model: &mut GameModel, args: &[&str]) -> Result<String> {
+    let id: u32 = arg(args, 0, "give <weapon>")?;
+    if id as usize >= model.assets.weapons.len() {
+        return Err(GameError::InvalidCommand(format!("no weapon with id {id}")));
+    }
+    model.player.weapon = Weapon::new(id, &model.assets.weapons);
+    Ok(format!("gave weapon {id}"))
+}
+
+fn complete_give(model: &GameModel, index: usize) -> Vec<String> {
+    match index {
+        0 => ids(model.assets.weapons.len()),
+        _ => Vec::new(),
+    }
+}
+
+fn cmd_god(model: &mut GameModel, _args: &[&str]) -> Result<String> {
+    model.player.god = !model.player.god;
+    Ok(format!(
+        "god mode {}",
+        if model.player.god { "on" } else { "off" }
+    ))
+}
+
+fn cmd_setmap(model: &mut GameModel, args: &[&str]) -> Result<String> {
+    let id: u32 = arg(args, 0, "setmap <id>")?;
+    model.assets.map(id)?;
+    model.world.map = id;
+    model.world.horde.clear();
+    model.status.run.level = id + 1;
+    // the new map may be smaller, so put the player back inside it.
+    let pos = model.world.player_pos;
+    model.set_x(pos.x);
+    model.set_y(pos.y);
+    let map_size = model.world.map_size(&model.assets);
+    model.renderer.camera.snap(model.world.player_pos, map_size);
+    model.audio.play_music(id);
+    Ok(format!("moved to map {id}"))
+}
+
+fn complete_setmap(model: &GameModel, index: usize) -> Vec<String> {
+    match index {
+        0 => ids(model.assets.maps.len()),
+        _ => Vec::new(),
+    }
+}
+
+fn cmd_tp(model: &mut GameModel, args: &[&str]) -> Result<String> {
+    const USAGE: &str = "tp <x> <y>";
+    let (x, y) = (arg(args, 0, USAGE)?, arg(args, 1, USAGE)?);
+    model.set_x(x);
+    model.set_y(y);
+    Ok(format!("teleported to {}", model.world.player_pos))
+}
+
+fn cmd_kill_all(model: &mut GameModel, _args: &[&str]) -> Result<String> {
+    // dying enemies play their death animation and are counted in `DEFEATED` as usual.
+    let mut killed = 0;
+    for enemy in model.world.horde.iter_mut().filter(|el| el.health > 0.) {
+        enemy.health = 0.;
+        killed += 1;
+    }
+    Ok(format!("killed {killed} enemies"))
+}
+
+fn cmd_timescale(model: &mut GameModel, args: &[&str]) -> Result<String> {
+    if args.is_empty() {
+        return Ok(format!("timescale is {}", model.time_scale));
+    }
+    let scale: f32 = arg(args, 0, "timescale [scale]")?;
+    if !(0. ..=MAX_TIME_SCALE).contains(&scale) {
+        return Err(GameError::InvalidCommand(format!(
+            "scale must be between 0 and {MAX_TIME_SCALE}"
+        )));
+    }
+    model.time_scale = scale;
+    Ok(format!("timescale set to {scale}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::tests::headless;
+
+    fn console_with_history(lines: &[&str]) -> Console {
+        let mut console = Console::new();
+        for line in lines {
+            console.input = (*line).to_owned();
+            console.take_input();
+        }
+        console
+    }
+
+    fn completed(model: &mut GameModel, input: &str) -> String {
+        model.console.input = input.to_owned();
+        model.complete_command();
+        model.console.input.clone()
+    }
+
+    #[test]
+    fn a_single_match_is_filled_in() {
+        let mut model = headless();
+        assert_eq!(completed(&mut model, "ti"), "timescale ");
+        // the stub assets have one enemy kind.
+        assert_eq!(completed(&mut model, "spawn "), "spawn 0 ");
+        assert_eq!(completed(&mut model, "  god"), "  god ");
+    }
+
+    #[test]
+    fn ambiguous_prefixes_extend_to_the_common_part() {
+        let mut model = headless();
+        assert_eq!(completed(&mut model, "prof"), "profile_");
+        assert_eq!(
+            model.console.output().back().unwrap(),
+            "profile_dump  profile_reset"
+        );
+        // nothing in common beyond what was typed: the input stays, the choices are listed.
+        assert_eq!(completed(&mut model, "s"), "s");
+        assert_eq!(model.console.output().back().unwrap(), "setmap  spawn");
+        // the common part ends on a char boundary.
+        model.console.input.clear();
+        model
+            .console
+            .complete_with(vec!["äbc".into(), "äbd".into(), "äx".into()]);
+        assert_eq!(model.console.input, "ä");
+    }
+
+    #[test]
+    fn no_match_leaves_the_input_alone() {
+        let mut model = headless();
+        let printed = model.console.output().len();
+        assert_eq!(completed(&mut model, "xyz"), "xyz");
+        assert_eq!(completed(&mut model, "god "), "god ");
+        assert_eq!(completed(&mut model, "nope "), "nope ");
+        assert_eq!(model.console.output().len(), printed);
+    }
+
+    #[test]
+    fn history_skips_blank_and_repeated_lines() {
+        let console = console_with_history(&["a", "  ", "b", "b", "a"]);
+        assert_eq!(console.history, ["a", "b", "a"]);
+    }
+
+    #[test]
+    fn browsing_history_stops_at_both_ends() {
+        let mut console = console_with_history(&[]);
+        console.browse_history(true);
+        assert_eq!(console.input, "");
+        console.browse_history(false);
+        assert_eq!(console.input, "");
+
+        let mut console = console_with_history(&["a", "b", "c"]);
+        for expected in ["c", "b", "a", "a", "a"] {
+            console.browse_history(true);
+            assert_eq!(console.input, expected);
+        }
+        for expected in ["b", "c", "", ""] {
+            console.browse_history(false);
+            assert_eq!(console.input, expected);
+        }
+        // past the newest line, going back starts over from it.
+        console.browse_history(true);
+        assert_eq!(console.input, "c");
+    }
+}
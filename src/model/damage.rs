@@ -19,9 +19,9 @@ pub trait Damageable {
 pub struct Damage<'a> {
     /// raw attack power value
     raw: f32,
-    /// attack buff
+    /// attack buff, multiplies `raw` by `1 + buff`
     buff: f32,
-    /// weapon being used
+    /// weapon being used, rolls its crit chance
     weapon: Option<&'a Weapon>,
     /// always crit
     crit_override: bool,
@@ -31,27 +31,106 @@ pub struct Damage<'a> {
     pub stun: f32,
 }
 
+/// Outcome of `Damage::evaluate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub amount: f32,
+    pub crit: bool,
+}
+
 impl<'a> Damage<'a> {
-    /// evaluate damage based on parameters
-    pub fn evaluate(&self) -> Result<f32> {
+    /// one swing of `weapon`, dealing its power
+    pub fn swing(weapon: &'a Weapon) -> Self {
+        Damage {
+            raw: weapon.power,
+            buff: 0.,
+            weapon: Some(weapon),
+            crit_override: false,
+            calculated: None,
+            stun: weapon.stun,
+        }
+    }
+    /// evaluate damage based on parameters. A cached value never crits.
+    pub fn evaluate(&self) -> Result<Hit> {
         match self.calculated {
-            Some(s) => Ok(s),
+            Some(s) => Ok(Hit {
+                amount: s,
+                crit: false,
+            }),
             None => {
-                let mut result = self.raw;
-                if let Some(w) = self.weapon {
-                    result *= 1.0 + w.power;
-                }
-                result *= if self.crit_override
+                let crit = self.crit_override
                     || self
                         .weapon
-                        .is_some_and(|el| random::<f32>() < el.crit_chance)
-                {
-                    CRIT_MULTIPLIER
-                } else {
-                    1.0
-                };
-                Ok(result)
+                        .is_some_and(|el| random::<f32>() < el.crit_chance);
+                let mut amount = self.raw * (1.0 + self.buff);
+                if crit {
+                    amount *= CRIT_MULTIPLIER;
+                }
+                Ok(Hit { amount, crit })
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::weapons::weaponmap::WeaponMap;
+
+    fn weapon(crit_chance: f32) -> Weapon {
+        let mut weapon = Weapon::new(0, &WeaponMap::stub());
+        weapon.power = 10.;
+        weapon.crit_chance = crit_chance;
+        weapon
+    }
+
+    #[test]
+    fn swings_deal_the_weapon_power() {
+        let weapon = weapon(0.);
+        for _ in 0..100 {
+            let hit = Damage::swing(&weapon).evaluate().unwrap();
+            assert_eq!(
+                hit,
+                Hit {
+                    amount: 10.,
+                    crit: false
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn crits_multiply_the_damage() {
+        let weapon = weapon(1.);
+        let hit = Damage::swing(&weapon).evaluate().unwrap();
+        assert_eq!(
+            hit,
+            Hit {
+                amount: 10. * CRIT_MULTIPLIER,
+                crit: true
+            }
+        );
+        let forced = Damage {
+            crit_override: true,
+            buff: 0.5,
+            ..Damage::swing(&weapon)
+        };
+        assert_eq!(forced.evaluate().unwrap().amount, 15. * CRIT_MULTIPLIER);
+    }
+
+    #[test]
+    fn cached_damage_is_used_as_is() {
+        let weapon = weapon(1.);
+        let cached = Damage {
+            calculated: Some(3.),
+            ..Damage::swing(&weapon)
+        };
+        assert_eq!(
+            cached.evaluate().unwrap(),
+            Hit {
+                amount: 3.,
+                crit: false
+            }
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Display,
+};
+
+/// Frame times kept for the graph.
+pub const FRAME_HISTORY: usize = 240;
+
+/// Groups of watch values, drawn in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Perf,
+    /// Phase timings from `profiler`.
+    Profile,
+    Player,
+    Horde,
+    Input,
+}
+
+impl Section {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Perf => "PERF",
+            Self::Profile => "PROFILE",
+            Self::Player => "PLAYER",
+            Self::Horde => "HORDE",
+            Self::Input => "INPUT",
+        }
+    }
+}
+
+/// World-space visualisations drawn over the game.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugToggles {
+    /// Player damage radius and enemy locations.
+    pub hitboxes: bool,
+    /// Weapon hit area, with the enemies currently inside it.
+    pub weapon_cone: bool,
+    /// World grid and the rectangle used to cull enemies.
+    pub grid: bool,
+}
+
+/// Everything the debug overlay shows. Values persist until overwritten, so things that
+/// only change now and then don't flicker.
+pub struct DebugOverlay {
+    /// Whether the text panel and frame graph are drawn.
+    pub visible: bool,
+    pub toggles: DebugToggles,
+    /// Watch values by section, in the order they were first set.
+    watches: BTreeMap<Section, Vec<(&'static str, String)>>,
+    /// Seconds per frame, oldest first.
+    frame_times: VecDeque<f32>,
+}
+
+impl DebugOverlay {
+    pub fn new(visible: bool) -> Self {
+        DebugOverlay {
+            visible,
+            toggles: DebugToggles::default(),
+            watches: BTreeMap::new(),
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+        }
+    }
+    /// Sets the watch value `name` in `section`.
+    pub fn watch(&mut self, section: Section, name: &'static str, value: impl Display) {
+        let entries = self.watches.entry(section).or_default();
+        let value = value.to_string();
+        match entries.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, old)) => *old = value,
+            None => entries.push((name, value)),
+        }
+    }
+    pub fn record_frame(&mut self, dt: f32) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(dt);
+    }
+    pub fn sections(&self) -> impl Iterator<Item = (&Section, &Vec<(&'static str, String)>)> {
+        self.watches.iter()
+    }
+    pub fn frame_times(&self) -> &VecDeque<f32> {
+        &self.frame_times
+    }
+}
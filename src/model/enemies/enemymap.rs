@@ -1,17 +1,30 @@
-use std::path::PathBuf;
+use std::{fs::File, path::PathBuf};
 
-use futures::executor::block_on;
 use macroquad::prelude::*;
-use once_cell::sync::Lazy;
+use serde::Deserialize;
 
-use crate::errors::Result;
+use crate::{
+    assets::{
+        ASSET_LOC, ENEMY_LOC, PLACEHOLDER_SIZE, TextureAtlas, parse_frame_name, placeholder_image,
+        read_dir_sorted,
+    },
+    errors::{GameError, Result},
+    model::animation::{AnimState, Clip, Clips},
+};
 
+/// Every enemy kind, indexed by enemy id. Part of `assets::Assets`.
 pub struct EnemyMap {
     map: Vec<EnemyKind>,
+    /// Every animation frame of every enemy kind. `None` for stub assets.
+    atlas: Option<Texture2D>,
 }
 
+/// Stats come from `assets/enemies.json`, where the array index is the enemy id.
+#[derive(Deserialize)]
 pub struct EnemyKind {
-    pub animation: Vec<(f32, Texture2D)>,
+    /// Clips whose frames are source rectangles inside the enemy atlas.
+    #[serde(skip)]
+    pub animation: Clips<Rect>,
     pub cooldown: f32,
     pub attack: f32,
     pub health: f32,
@@ -19,70 +32,130 @@ pub struct EnemyKind {
     pub stunnable: bool,
 }
 
-static ENEMYMAP: Lazy<EnemyMap> = Lazy::new(EnemyMap::init_sync);
+pub const ENEMY_DATA_LOC: &str = "enemies.json";
+/// Seconds each enemy animation frame is shown.
+const FRAME_TIME: f32 = 0.1;
 
-pub fn get_enemy_info(id: u32) -> Option<&'static EnemyKind> {
-    ENEMYMAP.map.get(id as usize)
-}
+impl EnemyMap {
+    pub fn get(&self, id: u32) -> Option<&EnemyKind> {
+        self.map.get(id as usize)
+    }
+
+    /// Number of enemy kinds. Ids run from 0 to this.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
 
-const TYPES: usize = 2;
+    /// The texture every enemy frame is drawn from.
+    pub fn atlas(&self) -> Option<&Texture2D> {
+        self.atlas.as_ref()
+    }
+
+    /// Largest animation frame across all enemy kinds, in world units.
+    pub fn max_size(&self) -> Vec2 {
+        self.map
+            .iter()
+            .flat_map(|kind| kind.animation.iter())
+            .flat_map(|(_, clip)| clip.frames.iter())
+            .fold(Vec2::ZERO, |acc, (_, rect)| acc.max(rect.size()))
+    }
+
+    fn load_stats() -> Result<Vec<EnemyKind>> {
+        let path = PathBuf::from(ASSET_LOC).join(ENEMY_DATA_LOC);
+        let file = File::open(&path).map_err(|_| GameError::NotFoundError(path.clone()))?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::AssetLoadFailure(format!("{}: {e}", path.display())))
+    }
 
-impl EnemyMap {
     async fn init() -> Result<Self> {
-        let mut textures = vec![];
+        let mut contents = Self::load_stats()?;
+        let files = read_dir_sorted(&PathBuf::from(ASSET_LOC).join(ENEMY_LOC));
 
-        for i in 0..TYPES {
-            let mut file_futures = vec![];
-            PathBuf::from("assets/enemies/")
-                .read_dir()
-                .unwrap()
-                .filter(|el| {
-                    el.as_ref()
-                        .unwrap()
-                        .file_name()
-                        .to_str()
-                        .unwrap()
-                        .starts_with(&i.to_string())
-                })
-                .map(async |el| {
-                    (
-                        0.1,
-                        Texture2D::from_file_with_format(
-                            &load_file(el.unwrap().path().as_os_str().to_str().unwrap())
-                                .await
-                                .unwrap(),
-                            None,
-                        ),
-                    )
-                })
-                .for_each(|el| file_futures.push(el));
-            let mut tex = Vec::with_capacity(file_futures.len());
+        // (kind, state, frame index) of every image, parsed from names like `0idle3.png`.
+        let mut frames = Vec::new();
+        let mut images = Vec::new();
+        for path in &files {
+            let Some(stem) = path.file_stem().and_then(|el| el.to_str()) else {
+                continue;
+            };
+            let parsed = parse_frame_name(stem)
+                .and_then(|(id, name, frame)| Some((id?, AnimState::from_name(name)?, frame)));
+            let Some((id, state, frame)) = parsed else {
+                warn!(
+                    "Skipping enemy frame with unexpected name: {}",
+                    path.display()
+                );
+                continue;
+            };
+            let image = match load_file(&path.to_string_lossy()).await {
+                Ok(bytes) => Image::from_file_with_format(&bytes, None).map_err(GameError::from),
+                Err(e) => Err(e.into()),
+            };
+            images.push(image.unwrap_or_else(|e| {
+                error!("Using placeholder for {}: {e}", path.display());
+                placeholder_image(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE)
+            }));
+            frames.push((id as usize, state, frame));
+        }
+        let atlas = TextureAtlas::pack(&images)?;
 
-            for i in file_futures {
-                tex.push(i.await);
+        for (kind, clips) in contents.iter_mut().map(|el| &mut el.animation).enumerate() {
+            for state in AnimState::ALL {
+                let mut clip_frames: Vec<_> = frames
+                    .iter()
+                    .zip(&atlas.rects)
+                    .filter(|((id, s, _), _)| *id == kind && *s == state)
+                    .map(|((_, _, frame), rect)| (*frame, *rect))
+                    .collect();
+                if clip_frames.is_empty() {
+                    continue;
+                }
+                clip_frames.sort_unstable_by_key(|(frame, _)| *frame);
+                clips.insert(
+                    state,
+                    Clip::new(
+                        clip_frames
+                            .into_iter()
+                            .map(|(_, rect)| (FRAME_TIME, rect))
+                            .collect(),
+                        state.default_mode(),
+                    ),
+                );
             }
-            textures.push(tex);
         }
-        let mut contents = Vec::new();
-        contents.push(EnemyKind {
-            animation: textures.remove(0),
-            cooldown: 0.5,
-            attack: 1.,
-            health: 20.,
-            speed: 50.,
-            stunnable: false,
-        });
-        contents.push(EnemyKind {
-            animation: textures.remove(0),
-            cooldown: 1.,
-            attack: 2.,
-            health: 30.,
-            speed: 40.,
-            stunnable: false,
-        });
-        Ok(EnemyMap { map: contents })
+        Ok(EnemyMap {
+            map: contents,
+            atlas: Some(atlas.texture),
+        })
     }
-    fn init_sync() -> Self {
-        block_on(Self::init()).unwrap()
+    /// Falls back to no enemy kinds at all if the stats can't be read, so nothing spawns.
+    pub async fn load() -> Self {
+        Self::init().await.unwrap_or_else(|e| {
+            error!("Failed to load enemies: {e}");
+            EnemyMap {
+                map: Vec::new(),
+                atlas: None,
+            }
+        })
+    }
+    /// One kind of enemy with no textures, idling on the single frame `frame`.
+    #[cfg(test)]
+    pub fn stub(frame: Rect) -> Self {
+        let mut animation = Clips::new();
+        animation.insert(
+            AnimState::Idle,
+            Clip::new(vec![(FRAME_TIME, frame)], AnimState::Idle.default_mode()),
+        );
+        EnemyMap {
+            map: vec![EnemyKind {
+                animation,
+                cooldown: 0.5,
+                attack: 1.,
+                health: 20.,
+                speed: 50.,
+                stunnable: false,
+            }],
+            atlas: None,
+        }
     }
 }
@@ -1,31 +1,56 @@
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
 
-use ::rand::random;
-use macroquad::{prelude::*, telemetry::frame};
+use macroquad::prelude::*;
 use rayon::prelude::*;
 
 use crate::errors::Nresult;
 
-use super::damage::{Damageable, Effects};
+use super::{
+    animation::{AnimState, Animator, Clips},
+    damage::{Damageable, Effects},
+};
 
 pub mod enemymap;
 
+/// Random points `HordeEnemies::spawn_around` tries before settling for the farthest one.
+const SPAWN_TRIES: u32 = 64;
+
+use enemymap::EnemyMap;
+
 #[derive(Clone)]
 pub struct Enemy {
     pub id: u32,
     pub loc: Vec2,
     pub velocity: Vec2,
-    pub animation: f32,
+    pub anim: Animator,
     pub health: f32,
     pub stun_timer: f32,
 }
 
+impl Enemy {
+    /// Animation state this enemy should be in, given its health, hits and movement.
+    fn anim_state(&self, clips: &Clips<Rect>) -> AnimState {
+        if self.health <= 0. {
+            AnimState::Death
+        } else if self.anim.state == AnimState::Hurt && !self.anim.finished(clips) {
+            AnimState::Hurt
+        } else if self.velocity != Vec2::ZERO {
+            AnimState::Walk
+        } else {
+            AnimState::Idle
+        }
+    }
+}
+
 impl Damageable for Enemy {
     fn take_damage(&mut self, damage: super::damage::Damage, effects: Vec<Effects>) -> Nresult {
-        self.take_damage_raw(damage.evaluate()?)
+        self.take_damage_raw(damage.evaluate()?.amount)
     }
     fn take_damage_raw(&mut self, damage: f32) -> Nresult {
         self.health -= damage;
+        if self.health > 0. {
+            self.anim.set_state(AnimState::Hurt);
+        }
         Ok(())
     }
 }
@@ -120,6 +145,20 @@ impl HordeEnemies {
         self.enemies.get_mut(idx)
     }
 
+    /// Index range of enemies with `min_y <= loc.y <= max_y`. Only valid while sorted by y.
+    pub fn y_range(&self, min_y: f32, max_y: f32) -> Range<usize> {
+        let start = self.enemies.partition_point(|el| el.loc.y < min_y);
+        let end = start + self.enemies[start..].partition_point(|el| el.loc.y <= max_y);
+        start..end
+    }
+
+    /// Enemies whose location lies inside `rect`, in draw (y) order. Only valid while sorted by y.
+    pub fn query_rect(&self, rect: Rect) -> impl Iterator<Item = &Enemy> {
+        self.enemies[self.y_range(rect.top(), rect.bottom())]
+            .iter()
+            .filter(move |el| el.loc.x >= rect.left() && el.loc.x <= rect.right())
+    }
+
     pub fn sort_y(&mut self) {
         self.enemies.par_sort_unstable_by(|a, b| {
             a.loc
@@ -147,38 +186,65 @@ impl HordeEnemies {
         });
     }
 
-    pub fn move_all_enemies_towards(&mut self, player: Vec2) -> Nresult {
-        let frametime = get_frame_time();
+    pub fn move_all_enemies_towards(
+        &mut self,
+        player: Vec2,
+        kinds: &EnemyMap,
+        frametime: f32,
+    ) -> Nresult {
         self.enemies.par_iter_mut().for_each(|enemy| {
-            if let Some(info) = enemymap::get_enemy_info(enemy.id) {
-                enemy.loc = Self::move_pt_towards(enemy.loc, player, info.speed, frametime);
+            if enemy.health <= 0. {
+                enemy.velocity = Vec2::ZERO;
+            } else if let Some(info) = kinds.get(enemy.id) {
+                let next = Self::move_pt_towards(enemy.loc, player, info.speed, frametime);
+                enemy.velocity = (player - enemy.loc).normalize_or_zero() * info.speed;
+                enemy.loc = next;
             }
         });
         Ok(())
     }
 
-    pub fn spawn_around(&mut self, player: Vec2, map_size: Vec2, min_dist: f32, id: u32) {
-        let mut loc = Vec2 {
-            x: rand::gen_range(0., map_size.x),
-            y: rand::gen_range(0., map_size.y),
-        };
-        while loc.distance(player) < min_dist {
-            loc = Vec2 {
-                x: random::<f32>() * map_size.x,
-                y: random::<f32>() * map_size.y,
-            };
+    /// Spawns enemy `id` at a random point of the map at least `min_dist` away from `player`.
+    /// The distance shrinks to fit small maps, and after `SPAWN_TRIES` misses the farthest
+    /// point tried is used, so this always returns.
+    pub fn spawn_around(
+        &mut self,
+        player: Vec2,
+        map_size: Vec2,
+        min_dist: f32,
+        id: u32,
+        kinds: &EnemyMap,
+    ) {
+        let min_dist = min_dist.min(map_size.length() / 2.);
+        let mut farthest = (f32::NEG_INFINITY, player);
+        for _ in 0..SPAWN_TRIES {
+            let loc = vec2(
+                rand::gen_range(0., map_size.x),
+                rand::gen_range(0., map_size.y),
+            );
+            let dist = loc.distance(player);
+            if dist >= min_dist {
+                farthest = (dist, loc);
+                break;
+            }
+            if dist > farthest.0 {
+                farthest = (dist, loc);
+            }
         }
-        self.append(id, loc);
+        self.append(id, farthest.1, kinds);
     }
 
-    pub fn append(&mut self, id: u32, loc: Vec2) {
-        let enemy_info = enemymap::get_enemy_info(id).unwrap();
+    /// Spawns enemy `id` at `loc`. Unknown ids are ignored.
+    pub fn append(&mut self, id: u32, loc: Vec2, kinds: &EnemyMap) {
+        let Some(health) = kinds.get(id).map(|el| el.health) else {
+            return;
+        };
         self.enemies.push(Enemy {
             id,
             loc,
             velocity: Vec2::ZERO,
-            animation: 0.0,
-            health: enemy_info.health,
+            anim: Animator::new(),
+            health,
             stun_timer: 0.0,
         });
     }
@@ -188,11 +254,32 @@ impl HordeEnemies {
         let norm = dist_vec.normalize();
         initial + norm * distance * time
     }
-    pub fn update_anim_frames(&mut self) {
-        let frame_time = get_frame_time();
+    /// Advances every enemy's animation. Returns how many enemies started dying this frame.
+    pub fn update_anim_frames(&mut self, kinds: &EnemyMap, frame_time: f32) -> u32 {
         self.enemies
             .par_iter_mut()
-            .for_each(|el| el.animation = (el.animation + frame_time) % 0.6);
+            .map(|el| {
+                let Some(info) = kinds.get(el.id) else {
+                    return 0;
+                };
+                let was_dead = el.anim.state == AnimState::Death;
+                el.anim.set_state(el.anim_state(&info.animation));
+                // enemy clips carry no frame events, and deaths are read from the state.
+                el.anim.update(&info.animation, frame_time);
+                (!was_dead && el.anim.state == AnimState::Death) as u32
+            })
+            .sum()
+    }
+
+    /// Drops enemies whose death animation has played out.
+    pub fn remove_finished(&mut self, kinds: &EnemyMap) {
+        self.enemies.retain(|el| {
+            el.health > 0.
+                || el.anim.state != AnimState::Death
+                || !kinds
+                    .get(el.id)
+                    .is_some_and(|info| el.anim.finished(&info.animation))
+        });
     }
 }
 
@@ -201,3 +288,54 @@ impl Default for HordeEnemies {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::Assets;
+
+    #[test]
+    fn query_rect_finds_exactly_the_enemies_inside() {
+        let assets = Assets::stub();
+        let mut horde = HordeEnemies::new();
+        for i in 0..400 {
+            let loc = vec2((i * 37 % 101) as f32 * 10., (i * 53 % 97) as f32 * 10.);
+            horde.append(0, loc, &assets.enemies);
+        }
+        // unknown ids are ignored.
+        horde.append(1, Vec2::ZERO, &assets.enemies);
+        assert_eq!(horde.len(), 400);
+        horde.sort();
+        let rect = Rect::new(120., 300., 250., 180.);
+        let found: Vec<Vec2> = horde.query_rect(rect).map(|el| el.loc).collect();
+        let expected = horde
+            .iter()
+            .filter(|el| {
+                (rect.left()..=rect.right()).contains(&el.loc.x)
+                    && (rect.top()..=rect.bottom()).contains(&el.loc.y)
+            })
+            .count();
+        assert_eq!(found.len(), expected);
+        assert!(found.windows(2).all(|el| el[0].y <= el[1].y));
+    }
+
+    #[test]
+    fn spawn_around_fits_small_maps() {
+        let assets = Assets::stub();
+        let mut horde = HordeEnemies::new();
+        // smaller than the spawn distance in every direction.
+        let map_size = vec2(500., 300.);
+        for player in [Vec2::ZERO, map_size / 2., map_size] {
+            horde.clear();
+            horde.spawn_around(player, map_size, 1000., 0, &assets.enemies);
+            let loc = horde[0].loc;
+            assert!(loc.cmpge(Vec2::ZERO).all() && loc.cmple(map_size).all());
+        }
+        horde.clear();
+        let big = vec2(5000., 5000.);
+        for _ in 0..100 {
+            horde.spawn_around(big / 2., big, 1000., 0, &assets.enemies);
+        }
+        assert!(horde.iter().all(|el| el.loc.distance(big / 2.) >= 1000.));
+    }
+}
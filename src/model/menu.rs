@@ -0,0 +1,103 @@
+use std::ops::Range;
+
+use macroquad::prelude::*;
+
+use crate::{
+    input::{InputMan, actions::Action},
+    renderer::menu::MenuLayout,
+};
+
+/// What the player did to a menu this frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuEvent {
+    /// Item with this index was chosen, by `MenuConfirm` or a click.
+    Confirm(u32),
+    Back,
+}
+
+/// Moves `selection` with the menu actions or the mouse, laid out as `layout` draws `items`.
+/// Hovering only selects when the mouse moved, so it doesn't fight the keyboard.
+pub fn navigate<S: AsRef<str>>(
+    selection: &mut u32,
+    items: &[S],
+    layout: &MenuLayout,
+    input: &mut InputMan,
+) -> Option<MenuEvent> {
+    navigate_window(selection, items, 0..items.len(), layout, input)
+}
+
+/// `navigate` for a scrolling list, where `layout` only draws the `shown` part of `items`.
+/// The keyboard still reaches every item, the mouse only the shown ones.
+pub fn navigate_window<S: AsRef<str>>(
+    selection: &mut u32,
+    items: &[S],
+    shown: Range<usize>,
+    layout: &MenuLayout,
+    input: &mut InputMan,
+) -> Option<MenuEvent> {
+    let last = (items.len() as u32).saturating_sub(1);
+    let hovered = layout
+        .hit(&items[shown.clone()], input.mouse.pos())
+        .map(|i| i + shown.start);
+    if input.mouse.moved()
+        && let Some(i) = hovered
+    {
+        *selection = i as u32;
+    }
+    if let Some(i) = hovered
+        && input.mouse.buttonpress(MouseButton::Left)
+    {
+        *selection = i as u32;
+        return Some(MenuEvent::Confirm(*selection));
+    }
+    if input.pressed(Action::MenuUp) {
+        *selection = selection.saturating_sub(1);
+    } else if input.pressed(Action::MenuDown) {
+        *selection = (*selection + 1).min(last);
+    } else if input.pressed(Action::MenuConfirm) {
+        return Some(MenuEvent::Confirm(*selection));
+    } else if input.pressed(Action::MenuBack) {
+        return Some(MenuEvent::Back);
+    }
+    None
+}
+
+/// First row to show of a list of `len` items with room for `rows`, starting from `scroll`.
+/// Moves as little as needed to keep `selection` in view.
+pub fn follow_selection(scroll: u32, selection: u32, rows: u32, len: u32) -> u32 {
+    let scroll = if selection < scroll {
+        selection
+    } else if selection >= scroll + rows {
+        selection + 1 - rows
+    } else {
+        scroll
+    };
+    scroll.min(len.saturating_sub(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling_keeps_the_selection_in_view() {
+        // moving inside the window doesn't scroll.
+        assert_eq!(follow_selection(0, 3, 5, 20), 0);
+        assert_eq!(follow_selection(4, 8, 5, 20), 4);
+        // moving past either edge scrolls just enough.
+        assert_eq!(follow_selection(0, 5, 5, 20), 1);
+        assert_eq!(follow_selection(6, 5, 5, 20), 5);
+        // jumps land with the selection at the edge it came from.
+        assert_eq!(follow_selection(0, 19, 5, 20), 15);
+        assert_eq!(follow_selection(15, 0, 5, 20), 0);
+    }
+
+    #[test]
+    fn scrolling_stops_at_the_end_of_the_list() {
+        // short lists never scroll.
+        assert_eq!(follow_selection(0, 2, 5, 3), 0);
+        // a shrunk list pulls the window back.
+        assert_eq!(follow_selection(10, 12, 5, 13), 8);
+        assert_eq!(follow_selection(3, 0, 5, 0), 0);
+    }
+}
@@ -1,31 +1,50 @@
 // IMPORTS
 
-use std::{
-    fs::DirEntry,
-    ops::Deref,
-    sync::{Arc, RwLock, atomic::AtomicU32},
-};
+use std::{path::Path, sync::atomic::AtomicU32};
 
+use animation::AnimEvent;
 use async_std::{path::PathBuf, stream::StreamExt};
+use console::Console;
 use damage::Damageable;
-use enemies::{HordeEnemies, enemymap::get_enemy_info};
-use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
+use debug::{DebugOverlay, Section};
+use enemies::HordeEnemies;
 use macroquad::{prelude::*, rand::ChooseRandom};
-use once_cell::sync::Lazy;
+use menu::MenuEvent;
 use player::Player;
+use save::{SAVE_CACHE, SaveData};
 
 use crate::{
+    assets::Assets,
+    audio::{AudioBackend, AudioMan, Sfx},
+    crash::{self, CrashContext},
     errors::{Nresult, Result},
-    input::InputMan,
-    renderer::Renderer,
-    util::{find_in_distance, get_mouse_angle},
+    input::{
+        InputMan,
+        actions::{Action, BINDINGS_LOC},
+    },
+    profiler,
+    renderer::{Renderer, ui::SAVE_MENU_ROWS},
+    settings::{SETTINGS_LOC, Settings, WindowMode},
+    util::{Clock, find_in_distance, unix_now},
+};
+#[cfg(debug_assertions)]
+use crate::{
+    assets::{ASSET_LOC, AssetWatcher},
+    model::weapons::weaponmap::WEAPON_DATA_LOC,
 };
+#[cfg(debug_assertions)]
+use futures::executor::block_on;
 pub use world::World;
 
+pub mod animation;
+pub mod console;
 mod damage;
+pub mod debug;
 pub mod enemies;
 mod entity;
+pub mod menu;
 pub mod player;
+pub mod save;
 pub mod weapons;
 pub mod world;
 
@@ -35,70 +54,208 @@ pub const SAVE_LOC: &str = "save/";
 pub const DAMAGE_DIST: f32 = 10.;
 
 /// Phase of title
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum TitlePhase {
     Start,
-    Menu(u32),
+    /// Save browser. `selection` 0 is "new game", the rest index `SAVE_CACHE`.
+    /// `scroll` is the first row shown.
+    Menu {
+        selection: u32,
+        scroll: u32,
+        edit: SaveEdit,
+    },
+    Settings(SettingsScreen),
+}
+
+/// What the save browser is doing with the selected save.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveEdit {
+    None,
+    /// Waiting for the player to confirm deletion.
+    Delete,
+    /// Typing a new name into `Status::rename`.
+    Rename,
+}
+
+/// State of the settings screen, reachable from the title and the pause menu.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SettingsScreen {
+    /// Indexes `SettingsRow::ALL`.
+    pub selection: u32,
+    /// The rebinding screen, open over the settings.
+    pub controls: Option<ControlsScreen>,
+}
+
+/// Rows of the settings screen. Values are filled in by `ui::settings_menu_items`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsRow {
+    Window,
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    DebugOverlay,
+    Zoom,
+    Letterbox,
+    Controls,
+    Back,
+}
+
+impl SettingsRow {
+    /// Every row, top to bottom.
+    pub const ALL: [SettingsRow; 9] = [
+        Self::Window,
+        Self::MasterVolume,
+        Self::MusicVolume,
+        Self::SfxVolume,
+        Self::DebugOverlay,
+        Self::Zoom,
+        Self::Letterbox,
+        Self::Controls,
+        Self::Back,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Window => "Window",
+            Self::MasterVolume => "Master volume",
+            Self::MusicVolume => "Music volume",
+            Self::SfxVolume => "Effects volume",
+            Self::DebugOverlay => "Debug overlay at start",
+            Self::Zoom => "Zoom",
+            Self::Letterbox => "Letterbox",
+            Self::Controls => "Controls",
+            Self::Back => "Back",
+        }
+    }
+}
+
+/// State of the rebinding screen, reachable from the settings screen.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ControlsScreen {
+    /// Indexes `Action::ALL`, one past the end is "reset to defaults".
+    pub selection: u32,
+    /// The next key or button pressed becomes the binding of the selected action.
+    pub waiting: bool,
+    /// Action that kept the last rebind from happening.
+    pub conflict: Option<Action>,
 }
 
 /// current phase of game
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum GameMode {
-    Title { phase: TitlePhase },
+    Title {
+        phase: TitlePhase,
+    },
     Play,
-    Pause,
-    GameOver,
+    /// `selection` indexes `PAUSE_OPTIONS`. `settings` is open over the pause menu.
+    Pause {
+        selection: u32,
+        settings: Option<SettingsScreen>,
+    },
+    /// `selection` indexes `GAMEOVER_OPTIONS`.
+    GameOver {
+        selection: u32,
+    },
 }
 
-/// cache of save files
-pub static SAVE_PATHBUF_CACHE: Lazy<RwLock<Vec<std::path::PathBuf>>> = Lazy::new(|| {
-    RwLock::new(
-        std::path::PathBuf::from(SAVE_LOC)
-            .read_dir()
-            .unwrap()
-            .map(|el| el.unwrap())
-            .map(|el| DirEntry::path(&el))
-            .collect(),
-    )
-});
+pub const GAMEOVER_OPTIONS: [&str; 2] = ["RESTART", "RETURN TO TITLE"];
+pub const PAUSE_OPTIONS: [&str; 4] = ["RESUME", "SETTINGS", "SAVE & QUIT TO TITLE", "QUIT"];
+pub const STARTING_LIVES: u32 = 5;
 
 /// The GameModel is responsible for generating data.
 pub struct GameModel {
-    pub debug: Option<Arc<UnboundedSender<String>>>,
+    pub debug: DebugOverlay,
+    pub console: Console,
+    /// Multiplies the frame time of everything in gameplay. Set with the `timescale` command.
+    pub time_scale: f32,
+    /// Seed of macroquad's `rand`, recorded in crash reports.
+    pub seed: u64,
     pub status: Status,
     pub world: World,
     pub player: Player,
     pub renderer: Renderer,
     pub input: InputMan,
+    pub assets: Assets,
+    pub settings: Settings,
+    pub audio: AudioMan,
+    clock: Box<dyn Clock>,
+    #[cfg(debug_assertions)]
+    watcher: AssetWatcher,
 }
 
 pub static DEFEATED: AtomicU32 = AtomicU32::new(0);
 
 impl GameModel {
-    pub fn new() -> Self {
+    /// Everything that needs a window comes in from outside: `audio`, the gamepad in `input` and
+    /// the `clock`. The window itself is left to the main loop.
+    pub fn new(
+        assets: Assets,
+        settings: Settings,
+        audio: Box<dyn AudioBackend>,
+        input: InputMan,
+        clock: Box<dyn Clock>,
+    ) -> Self {
+        let mut renderer = Renderer::new();
+        settings.apply(&mut renderer.camera);
+        // nanoseconds, so games started in the same second still get different seeds.
+        let seed = unix_now().as_nanos() as u64;
+        rand::srand(seed);
+        let mut console = Console::new();
+        for command in profiler::commands() {
+            console.commands.register(command);
+        }
+        info!("Random seed: {seed}");
+        let player = Player::starting(&assets.weapons);
         GameModel {
-            debug: None,
+            debug: DebugOverlay::new(settings.debug_overlay),
+            console,
+            time_scale: 1.,
+            seed,
             status: Status {
                 health: 100,
-                lives: 5,
+                lives: STARTING_LIVES,
                 mode: GameMode::Title {
                     phase: TitlePhase::Start,
                 },
+                run: RunStats::default(),
+                rename: String::new(),
             },
-            world: World {
-                player_pos: vec2(0.0, 0.0),
-                horde: HordeEnemies::new(),
-                map: 0,
-            },
-            player: Player::default(),
-            renderer: Renderer::new(),
-            input: InputMan::new(),
+            world: World::new(),
+            player,
+            renderer,
+            input,
+            assets,
+            audio: AudioMan::new(audio, (&settings).into()),
+            settings,
+            clock,
+            #[cfg(debug_assertions)]
+            watcher: AssetWatcher::new(),
         }
     }
     pub fn init(&mut self) -> Nresult {
-        self.player = Player::default();
+        self.player = Player::starting(&self.assets.weapons);
         Ok(())
     }
+    /// Puts the player back at the start of the current map with fresh health, lives and stats.
+    pub fn reset_run(&mut self) {
+        self.player = Player::starting(&self.assets.weapons);
+        self.world.horde.clear();
+        self.world.player_pos = Vec2::ZERO;
+        self.status.health = 100;
+        self.status.lives = STARTING_LIVES;
+        self.status.run = RunStats {
+            level: self.world.map + 1,
+            ..Default::default()
+        };
+        DEFEATED.store(0, std::sync::atomic::Ordering::Release);
+        let map_size = self.world.map_size(&self.assets);
+        self.renderer.camera.snap(self.world.player_pos, map_size);
+    }
+    /// Announces the current map and starts its music. Call when gameplay (re)starts.
+    fn start_level(&mut self) {
+        self.audio.play(Sfx::LevelStart);
+        self.audio.play_music(self.world.map);
+    }
     pub async fn can_continue() -> bool {
         PathBuf::from(SAVE_LOC).read_dir().await.iter().len() != 0
     }
@@ -113,144 +270,596 @@ impl GameModel {
         Ok(ret)
     }
     pub async fn call_render(&mut self) {
-        self.renderer.render_world(&self.world, &self.player).await;
-        self.renderer.render_ui(&self.status, &self.player).await;
+        let _scope = profiler::scope("render");
+        {
+            let _scope = profiler::scope("render_world");
+            let attacking = self.input.down(Action::Attack);
+            self.renderer
+                .render_world(
+                    &self.world,
+                    &self.player,
+                    &self.assets,
+                    &self.debug,
+                    attacking,
+                )
+                .await;
+        }
+        {
+            let _scope = profiler::scope("render_ui");
+            self.renderer
+                .render_ui(
+                    &self.status,
+                    &self.world,
+                    &self.player,
+                    &self.assets,
+                    &self.settings,
+                    &self.input,
+                )
+                .await;
+        }
+        profiler::time("render_debug", || {
+            self.renderer.render_debug(&self.debug, &self.console)
+        });
     }
     pub fn update(&mut self) {
+        let _scope = profiler::scope("update");
+        #[cfg(debug_assertions)]
+        self.hot_reload();
         match self.status.mode {
             GameMode::Title { .. } => self.update_title(),
+            GameMode::Play if self.console.open => self.update_console(),
             GameMode::Play => self.update_gameplay(),
-            GameMode::Pause => self.update_pause(),
-            _ => {}
+            GameMode::Pause {
+                selection,
+                settings,
+            } => self.update_pause(selection, settings),
+            GameMode::GameOver { selection } => self.update_gameover(selection),
+        }
+        self.update_watches();
+        self.update_crash_context();
+    }
+    /// Saves the run in progress, if any, before the game is closed from outside.
+    pub fn shutdown(&mut self) {
+        if matches!(self.status.mode, GameMode::Play | GameMode::Pause { .. }) {
+            match self.save() {
+                Ok(path) => info!("Saved the run to {}", path.display()),
+                Err(e) => error!("Failed to save the run: {e}"),
+            }
+        }
+        self.audio.stop_music();
+    }
+    /// Tells the panic hook what is going on, so a crash report can say so and save the run.
+    fn update_crash_context(&self) {
+        let in_run = matches!(self.status.mode, GameMode::Play | GameMode::Pause { .. });
+        crash::set_context(CrashContext {
+            mode: Some(self.status.mode),
+            seed: self.seed,
+            run: in_run.then(|| self.save_data()),
+        });
+    }
+    /// Picks up edited assets without restarting the run.
+    #[cfg(debug_assertions)]
+    fn hot_reload(&mut self) {
+        // a batch of edited frames only needs one reload of their directory. The data files and
+        // the crosshair all sit in `ASSET_LOC` itself, so each of those is reloaded on its own.
+        let (files, mut frames): (Vec<_>, Vec<_>) = self
+            .watcher
+            .poll(self.clock.time())
+            .into_iter()
+            .partition(|el| el.parent() == Some(Path::new(ASSET_LOC)));
+        frames.dedup_by(|a, b| a.parent() == b.parent());
+        for path in files.into_iter().chain(frames) {
+            match block_on(self.assets.reload(&path)) {
+                Ok(()) => info!("Reloaded {}", path.display()),
+                Err(e) => warn!("Could not reload {}: {e}", path.display()),
+            }
+            if path.strip_prefix(ASSET_LOC).ok() == Some(Path::new(WEAPON_DATA_LOC)) {
+                let stats = self.assets.weapons.get(self.player.weapon.id);
+                self.player.weapon.apply_stats(&stats);
+            }
         }
     }
     fn update_title(&mut self) {
         if let GameMode::Title { phase } = self.status.mode {
             match phase {
                 TitlePhase::Start => {
-                    if self.input.kbd.keypress(KeyCode::Enter) {
+                    if self.input.pressed(Action::MenuConfirm) {
+                        save::refresh_saves();
                         self.status.mode = GameMode::Title {
-                            phase: TitlePhase::Menu(0),
+                            phase: TitlePhase::Menu {
+                                selection: 0,
+                                scroll: 0,
+                                edit: SaveEdit::None,
+                            },
                         }
-                    }
-                }
-                TitlePhase::Menu(selection) => {
-                    if self.input.kbd.keypress(KeyCode::Up) && selection != 0 {
+                    } else if self.input.pressed(Action::OpenSettings) {
                         self.status.mode = GameMode::Title {
-                            phase: TitlePhase::Menu(selection - 1),
+                            phase: TitlePhase::Settings(SettingsScreen::default()),
                         }
                     }
-                    if self.input.kbd.keypress(KeyCode::Down)
-                        && !SAVE_PATHBUF_CACHE.read().unwrap().is_empty()
-                        && selection != SAVE_PATHBUF_CACHE.read().unwrap().len() as u32 - 1
+                }
+                TitlePhase::Menu {
+                    selection,
+                    scroll,
+                    edit,
+                } => self.update_save_menu(selection, scroll, edit),
+                TitlePhase::Settings(screen) => {
+                    let phase = match self.update_settings(screen) {
+                        Some(screen) => TitlePhase::Settings(screen),
+                        None => TitlePhase::Start,
+                    };
+                    self.status.mode = GameMode::Title { phase };
+                }
+            }
+        }
+    }
+    fn update_save_menu(&mut self, mut selection: u32, scroll: u32, edit: SaveEdit) {
+        // only the selected save is copied out. Deleting and renaming rescan the directory,
+        // so the lock can't be held for the whole frame.
+        let (count, selected) = {
+            let saves = SAVE_CACHE.read().unwrap();
+            let selected = (selection as usize)
+                .checked_sub(1)
+                .and_then(|i| saves.get(i))
+                .cloned();
+            (saves.len(), selected)
+        };
+        let selected = selected.as_ref();
+        let mut edit = edit;
+        match edit {
+            SaveEdit::Delete => {
+                if self.input.pressed(Action::MenuConfirm) {
+                    if let Some(save) = selected
+                        && let Err(e) = save::delete_save(&save.path)
                     {
-                        self.status.mode = GameMode::Title {
-                            phase: TitlePhase::Menu(selection - 1),
-                        }
+                        error!("Failed to delete {}: {e}", save.path.display());
                     }
-                    if self.input.kbd.keypress(KeyCode::Enter) {
-                        self.status.mode = GameMode::Play;
+                    selection = selection.min(count.saturating_sub(1) as u32);
+                    edit = SaveEdit::None;
+                } else if self.input.pressed(Action::MenuBack) {
+                    edit = SaveEdit::None;
+                }
+            }
+            SaveEdit::Rename => {
+                self.status
+                    .rename
+                    .extend(self.input.kbd.chars().iter().filter(|el| !el.is_control()));
+                if self.input.kbd.keypress(KeyCode::Backspace) {
+                    self.status.rename.pop();
+                }
+                if self.input.pressed(Action::MenuConfirm) {
+                    if let Some(save) = selected
+                        && let Err(e) = save::rename_save(&save.path, &self.status.rename)
+                    {
+                        error!("Failed to rename {}: {e}", save.path.display());
                     }
+                    edit = SaveEdit::None;
+                } else if self.input.pressed(Action::MenuBack) {
+                    edit = SaveEdit::None;
                 }
-                _ => {}
             }
+            SaveEdit::None => {
+                let items = crate::renderer::ui::save_menu_items(&SAVE_CACHE.read().unwrap());
+                let layout = crate::renderer::ui::save_menu_layout();
+                let shown =
+                    scroll as usize..(scroll + SAVE_MENU_ROWS).min(items.len() as u32) as usize;
+                let wheel = self.input.mouse.wheel().y;
+                if wheel > 0. {
+                    selection = selection.saturating_sub(1);
+                } else if wheel < 0. {
+                    selection = (selection + 1).min(count as u32);
+                }
+                if selected.is_some() && self.input.pressed(Action::MenuDelete) {
+                    edit = SaveEdit::Delete;
+                } else if let Some(save) = selected
+                    && self.input.pressed(Action::MenuRename)
+                {
+                    self.status.rename = save.name.clone();
+                    edit = SaveEdit::Rename;
+                } else {
+                    match menu::navigate_window(
+                        &mut selection,
+                        &items,
+                        shown,
+                        &layout,
+                        &mut self.input,
+                    ) {
+                        Some(MenuEvent::Confirm(_)) => {
+                            // a new game starts on the first map, whatever was played before.
+                            self.world.map = 0;
+                            self.reset_run();
+                            if let Some(save) = selected {
+                                match &save.data {
+                                    Some(data) => self.apply_save(data),
+                                    None => error!("Could not read {}", save.path.display()),
+                                }
+                            }
+                            self.start_level();
+                            self.status.mode = GameMode::Play;
+                            return;
+                        }
+                        Some(MenuEvent::Back) => {
+                            self.status.mode = GameMode::Title {
+                                phase: TitlePhase::Start,
+                            };
+                            return;
+                        }
+                        None => {}
+                    }
+                }
+            }
+        }
+        let scroll = menu::follow_selection(scroll, selection, SAVE_MENU_ROWS, count as u32 + 1);
+        self.status.mode = GameMode::Title {
+            phase: TitlePhase::Menu {
+                selection,
+                scroll,
+                edit,
+            },
+        };
+    }
+    /// Handles one frame of the settings screen. `None` once the player backs out.
+    fn update_settings(&mut self, mut screen: SettingsScreen) -> Option<SettingsScreen> {
+        if let Some(controls) = screen.controls {
+            screen.controls = self.update_controls(controls);
+            return Some(screen);
+        }
+        let row = SettingsRow::ALL[screen.selection as usize];
+        let steps = if self.input.pressed(Action::MenuLeft) {
+            -1.
+        } else if self.input.pressed(Action::MenuRight) {
+            1.
+        } else {
+            0.
+        };
+        if steps != 0. {
+            self.change_setting(row, steps, false);
+            return Some(screen);
+        }
+        let items = crate::renderer::ui::settings_menu_items(&self.settings);
+        let layout = crate::renderer::ui::settings_menu_layout();
+        match menu::navigate(&mut screen.selection, &items, &layout, &mut self.input) {
+            Some(MenuEvent::Back) => return None,
+            Some(MenuEvent::Confirm(i)) => match SettingsRow::ALL[i as usize] {
+                SettingsRow::Controls => screen.controls = Some(ControlsScreen::default()),
+                SettingsRow::Back => return None,
+                row => self.change_setting(row, 1., true),
+            },
+            None => {}
+        }
+        Some(screen)
+    }
+    /// Steps the setting in `row`, then applies and saves it. Toggles ignore the direction.
+    /// Volumes stop at the ends, unless `wrap` sends a step past full back to silence.
+    fn change_setting(&mut self, row: SettingsRow, steps: f32, wrap: bool) {
+        let settings = &mut self.settings;
+        let step = |volume: &mut f32| {
+            if wrap {
+                Settings::cycle_volume(volume);
+            } else {
+                Settings::step_volume(volume, steps);
+            }
+        };
+        match row {
+            SettingsRow::Window => {
+                // the main loop sees the new mode and switches the window.
+                settings.window_mode = match settings.window_mode {
+                    WindowMode::Windowed => WindowMode::Fullscreen,
+                    WindowMode::Fullscreen => WindowMode::Windowed,
+                }
+            }
+            SettingsRow::MasterVolume => step(&mut settings.master_volume),
+            SettingsRow::MusicVolume => step(&mut settings.music_volume),
+            SettingsRow::SfxVolume => step(&mut settings.sfx_volume),
+            SettingsRow::DebugOverlay => settings.debug_overlay = !settings.debug_overlay,
+            SettingsRow::Zoom => settings.step_zoom(steps),
+            SettingsRow::Letterbox => settings.letterbox = !settings.letterbox,
+            SettingsRow::Controls | SettingsRow::Back => return,
+        }
+        self.settings.apply(&mut self.renderer.camera);
+        self.audio.set_volumes((&self.settings).into());
+        if let Err(e) = self.settings.save(Path::new(SETTINGS_LOC)) {
+            error!("Failed to save settings: {e}");
+        }
+    }
+    /// Handles one frame of the rebinding screen. `None` once the player backs out.
+    fn update_controls(&mut self, screen: ControlsScreen) -> Option<ControlsScreen> {
+        let ControlsScreen {
+            selection, waiting, ..
+        } = screen;
+        let reset = Action::ALL.len() as u32;
+        if waiting {
+            let Some(binding) = self.input.any_press() else {
+                return Some(screen);
+            };
+            let action = Action::ALL[selection as usize];
+            let conflict = self.input.actions.rebind(action, binding).err();
+            if conflict.is_none() {
+                self.save_bindings();
+            }
+            Some(ControlsScreen {
+                selection,
+                waiting: false,
+                conflict,
+            })
+        } else if self.input.pressed(Action::MenuBack) {
+            None
+        } else if self.input.pressed(Action::MenuUp) && selection != 0 {
+            Some(ControlsScreen {
+                selection: selection - 1,
+                ..Default::default()
+            })
+        } else if self.input.pressed(Action::MenuDown) && selection != reset {
+            Some(ControlsScreen {
+                selection: selection + 1,
+                ..Default::default()
+            })
+        } else if self.input.pressed(Action::MenuConfirm) {
+            if selection == reset {
+                self.input.actions.reset();
+                self.save_bindings();
+            }
+            Some(ControlsScreen {
+                selection,
+                waiting: selection != reset,
+                conflict: None,
+            })
+        } else {
+            Some(screen)
+        }
+    }
+    fn save_bindings(&self) {
+        if let Err(e) = self.input.actions.save(Path::new(BINDINGS_LOC)) {
+            error!("Failed to save bindings: {e}");
         }
     }
 
     fn update_gameplay(&mut self) {
-        let _ = self.log(&format!("MOUSE ANGLE: {}", get_mouse_angle()));
-        let (x, y) = mouse_position();
-        let _ = self.log(&format!("MOUSE POS: {} {}", x, y));
-        let _ = self.log(&format!("PLAYER POS: {}", self.world.player_pos));
         self.update_debug();
         self.catch_pause();
-        self.move_player();
-        self.update_enemies().expect("Should work.");
-        self.update_attack();
-        self.update_map();
-        self.update_damage();
-        self.world.horde.update_anim_frames();
+        if self.player.is_dead() {
+            self.update_dying();
+            return;
+        }
+        self.status.run.time += self.dt();
+        if let Some(aim) = self.input.aim(&self.renderer.camera, self.world.player_pos) {
+            self.player.aim = aim;
+        }
+        profiler::time("move_player", || self.move_player());
+        self.update_zoom();
+        profiler::time("update_enemies", || self.update_enemies()).expect("Should work.");
+        profiler::time("update_attack", || self.update_attack());
+        if let Err(e) = profiler::time("update_damage", || self.update_damage()) {
+            error!("Failed to apply damage: {e}");
+        }
+        profiler::time("update_animation", || self.update_animation());
     }
-    fn update_debug(&mut self) -> Nresult {
-        if self.input.kbd.keypress(KeyCode::Slash) {
-            crate::renderer::ui::LOG.fetch_not(std::sync::atomic::Ordering::Release);
+    /// Lets the death animation play out before showing the game over screen.
+    fn update_dying(&mut self) {
+        self.player.velocity = Vec2::ZERO;
+        self.update_animation();
+        if self.player.anim.finished(&self.assets.player) {
+            self.audio.stop_music();
+            self.status.mode = GameMode::GameOver { selection: 0 };
         }
-        Ok(())
+    }
+    fn update_gameover(&mut self, mut selection: u32) {
+        let layout = crate::renderer::ui::gameover_menu_layout();
+        let event = menu::navigate(&mut selection, &GAMEOVER_OPTIONS, &layout, &mut self.input);
+        self.status.mode = GameMode::GameOver { selection };
+        if let Some(MenuEvent::Confirm(choice)) = event {
+            self.reset_run();
+            if choice == 0 {
+                self.start_level();
+                self.status.mode = GameMode::Play;
+            } else {
+                self.status.mode = GameMode::Title {
+                    phase: TitlePhase::Start,
+                };
+            }
+        }
+    }
+    fn update_animation(&mut self) {
+        let died = self
+            .world
+            .horde
+            .update_anim_frames(&self.assets.enemies, self.dt());
+        if died > 0 {
+            self.audio.play(Sfx::EnemyDeath);
+        }
+        DEFEATED.fetch_add(died, std::sync::atomic::Ordering::Release);
+        self.world.horde.remove_finished(&self.assets.enemies);
+        let events = self.player.update_animation(&self.assets.player, self.dt());
+        if events.contains(&AnimEvent::Footstep) {
+            self.audio.play(Sfx::Footstep);
+        }
+    }
+    fn update_debug(&mut self) {
+        if self.input.pressed(Action::ToggleDebug) {
+            self.debug.visible = !self.debug.visible;
+        }
+        if self.input.pressed(Action::ToggleConsole) {
+            self.console.open = true;
+        }
+        let toggles = &mut self.debug.toggles;
+        if self.input.pressed(Action::ToggleHitboxes) {
+            toggles.hitboxes = !toggles.hitboxes;
+        }
+        if self.input.pressed(Action::ToggleWeaponCone) {
+            toggles.weapon_cone = !toggles.weapon_cone;
+        }
+        if self.input.pressed(Action::ToggleGrid) {
+            toggles.grid = !toggles.grid;
+        }
+        if self.input.pressed(Action::ToggleLetterbox) {
+            self.renderer.camera.toggle_letterbox();
+        }
+    }
+    /// Typing into the open console. Gameplay doesn't run meanwhile.
+    fn update_console(&mut self) {
+        if self.input.pressed(Action::ToggleConsole) || self.input.kbd.keypress(KeyCode::Escape) {
+            // the toggle key types a character too, which must not end up in the input.
+            self.console.open = false;
+            return;
+        }
+        self.console
+            .input
+            .extend(self.input.kbd.chars().iter().filter(|el| !el.is_control()));
+        if self.input.kbd.keypress(KeyCode::Backspace) {
+            self.console.input.pop();
+        }
+        if self.input.kbd.keypress(KeyCode::Tab) {
+            self.complete_command();
+        }
+        if self.input.kbd.keypress(KeyCode::Up) {
+            self.console.browse_history(true);
+        }
+        if self.input.kbd.keypress(KeyCode::Down) {
+            self.console.browse_history(false);
+        }
+        if self.input.kbd.keypress(KeyCode::Enter) {
+            self.submit_command();
+        }
+    }
+    /// Whether the system cursor should show. Every menu uses it, gameplay draws a crosshair.
+    pub fn cursor_visible(&self) -> bool {
+        !matches!(self.status.mode, GameMode::Play)
+    }
+    /// Seconds of game time that passed this frame.
+    pub fn dt(&self) -> f32 {
+        self.clock.frame_time() * self.time_scale
+    }
+    /// Refreshes the overlay's watch values. Skipped while hidden, formatting isn't free,
+    /// but frame times are always kept so the graph is full when it opens.
+    fn update_watches(&mut self) {
+        let dt = self.clock.frame_time();
+        self.debug.record_frame(dt);
+        if !self.debug.visible {
+            return;
+        }
+        let dbg = &mut self.debug;
+        dbg.watch(Section::Perf, "fps", self.clock.fps());
+        dbg.watch(Section::Perf, "frame", format!("{:.2} ms", dt * 1000.));
+        dbg.watch(Section::Perf, "time scale", self.time_scale);
+        for (name, stats) in profiler::stats() {
+            dbg.watch(
+                Section::Profile,
+                name,
+                format!(
+                    "avg {:.2} p95 {:.2} p99 {:.2} ms",
+                    stats.avg, stats.p95, stats.p99
+                ),
+            );
+        }
+        let player = &self.player;
+        dbg.watch(Section::Player, "pos", self.world.player_pos);
+        dbg.watch(Section::Player, "velocity", player.velocity);
+        dbg.watch(
+            Section::Player,
+            "aim",
+            format!("{:.1} deg", player.aim.to_degrees()),
+        );
+        dbg.watch(Section::Player, "health", player.health);
+        dbg.watch(Section::Player, "dashing", player.dashing());
+        dbg.watch(Section::Player, "invulnerable", player.invulnerable());
+        dbg.watch(Section::Player, "cooldown", player.weapon.cooldown_counter);
+        dbg.watch(Section::Horde, "enemies", self.world.horde.len());
+        dbg.watch(
+            Section::Horde,
+            "defeated",
+            DEFEATED.load(std::sync::atomic::Ordering::Relaxed),
+        );
+        dbg.watch(Section::Horde, "map", self.world.map);
+        dbg.watch(Section::Input, "mouse", self.input.mouse.pos());
+        dbg.watch(Section::Input, "movement", self.input.movement());
+        dbg.watch(Section::Input, "gamepad", self.input.pad.connected());
     }
     fn update_damage(&mut self) -> Nresult {
+        let health = self.player.health;
         if let Ok(o) = find_in_distance(&mut self.world.horde, self.world.player_pos, DAMAGE_DIST) {
-            o.iter().for_each(|el| {
-                self.player
-                    .take_damage_raw(get_enemy_info(el.id).unwrap().attack);
-            });
-        }
-        if self.player.health <= 0. {
-            if self.status.lives == 1 {
-                self.status.mode = GameMode::GameOver;
-            } else {
+            let kinds = &self.assets.enemies;
+            o.iter()
+                .filter(|el| el.health > 0.)
+                .filter_map(|el| kinds.get(el.id))
+                .for_each(|info| {
+                    let _ = self.player.take_damage_raw(info.attack);
+                });
+        }
+        // dying enemies and invulnerability don't hurt, so they don't shake either.
+        if self.player.health < health {
+            self.renderer.camera.shake(0.2);
+            self.audio.play(Sfx::PlayerHurt);
+        }
+        if self.player.is_dead() {
+            // on the last life, update_dying takes over until the death animation is done.
+            if self.status.lives > 1 {
                 self.player.health = self.player.max_health;
                 self.status.lives -= 1;
                 self.world.horde.clear();
             }
-            show_mouse(true);
         }
-        self.log(&format!("health: {}", self.player.health));
         Ok(())
     }
     fn update_attack(&mut self) {
-        self.player.weapon.adjust_cooldown();
-        self.log(&format!(
-            "cooldown: {}",
-            self.player.weapon.cooldown_counter
-        ));
-        if is_mouse_button_down(MouseButton::Left) {
-            let _ = self.player.weapon.attack(&mut self.world);
+        self.player.weapon.adjust_cooldown(self.dt());
+        if self.input.down(Action::Attack)
+            && let Ok(outcome) = self.player.weapon.attack(&mut self.world, self.player.aim)
+            && outcome.swung
+        {
+            self.status.run.damage_dealt += outcome.dealt;
+            self.audio.play(Sfx::Swing);
+            if outcome.hits > 0 {
+                self.audio
+                    .play(if outcome.crit { Sfx::Crit } else { Sfx::Hit });
+            }
         }
     }
-    fn update_map(&mut self) {
-        clear_background(GRAY);
-    }
     fn update_enemies(&mut self) -> Nresult {
-        if self.input.kbd.keydown(KeyCode::Space) {
-            self.world
-                .horde
-                .spawn_around(self.world.player_pos, self.world.map_size(), 1000., 0);
+        let map_size = self.world.map_size(&self.assets);
+        if self.input.down(Action::DebugSpawn) {
+            self.world.horde.spawn_around(
+                self.world.player_pos,
+                map_size,
+                1000.,
+                0,
+                &self.assets.enemies,
+            );
         }
         let probability: Vec<u32> = vec![0, 0, 0, 0, 0, 1, 1, 1, 2, 2];
         let id = probability.choose().unwrap();
         if *id != 0 {
             self.world.horde.spawn_around(
                 self.world.player_pos,
-                self.world.map_size(),
+                map_size,
                 1000.,
                 id - 1,
+                &self.assets.enemies,
             );
         }
-        self.world
-            .horde
-            .move_all_enemies_towards(self.world.player_pos)?;
-        self.world.horde.sort();
-        DEFEATED.fetch_add(
-            self.world.horde.iter().filter(|el| el.health <= 0.).count() as u32,
-            std::sync::atomic::Ordering::Release,
-        );
-        self.world.horde.retain(|el| el.health > 0.);
+        self.world.horde.move_all_enemies_towards(
+            self.world.player_pos,
+            &self.assets.enemies,
+            self.dt(),
+        )?;
+        profiler::time("sort", || self.world.horde.sort());
         Ok(())
     }
     fn set_y(&mut self, y: f32) {
+        let map_size = self.world.map_size(&self.assets);
         if y < 0. {
             self.world.player_pos.y = 0.;
-        } else if y > self.world.map_size().y {
-            self.world.player_pos.y = self.world.map_size().y;
+        } else if y > map_size.y {
+            self.world.player_pos.y = map_size.y;
         } else {
             self.world.player_pos.y = y;
         }
     }
     fn set_x(&mut self, x: f32) {
+        let map_size = self.world.map_size(&self.assets);
         if x < 0. {
             self.world.player_pos.x = 0.;
-        } else if x > self.world.map_size().x {
-            self.world.player_pos.x = self.world.map_size().x;
+        } else if x > map_size.x {
+            self.world.player_pos.x = map_size.x;
         } else {
             self.world.player_pos.x = x;
         }
@@ -263,42 +872,108 @@ impl GameModel {
     }
     fn move_player(&mut self) {
         //TODO implement hitboxes and out of bounds
-        if self.input.kbd.keydown(KeyCode::W) {
-            self.move_y(-get_frame_time() * self.player.speed);
+        let dt = self.dt();
+        let input = self.input.movement();
+        if self.input.pressed(Action::Dash) {
+            self.player.dash(input);
         }
-        if self.input.kbd.keydown(KeyCode::S) {
-            self.move_y(get_frame_time() * self.player.speed);
-        }
-        if self.input.kbd.keydown(KeyCode::A) {
-            self.move_x(-get_frame_time() * self.player.speed);
-        }
-        if self.input.kbd.keydown(KeyCode::D) {
-            self.move_x(get_frame_time() * self.player.speed);
+        self.player.steer(input, dt);
+        let velocity = self.player.velocity;
+        self.move_x(velocity.x * dt);
+        self.move_y(velocity.y * dt);
+    }
+    fn update_zoom(&mut self) {
+        let wheel = self.input.mouse.wheel().y;
+        if wheel != 0. {
+            self.renderer.camera.zoom_by(wheel.signum());
         }
     }
     fn catch_pause(&mut self) {
-        if self.input.kbd.keypress(KeyCode::Escape) {
-            self.status.mode = GameMode::Pause;
+        if self.input.pressed(Action::Pause) {
+            self.status.mode = GameMode::Pause {
+                selection: 0,
+                settings: None,
+            };
         }
     }
-    fn update_pause(&mut self) {
-        if self.input.kbd.keypress(KeyCode::Escape) {
+    fn update_pause(&mut self, mut selection: u32, settings: Option<SettingsScreen>) {
+        if let Some(screen) = settings {
+            self.status.mode = GameMode::Pause {
+                selection,
+                settings: self.update_settings(screen),
+            };
+            return;
+        }
+        if self.input.pressed(Action::Pause) {
             self.status.mode = GameMode::Play;
+            return;
+        }
+        let layout = crate::renderer::ui::pause_menu_layout();
+        let event = menu::navigate(&mut selection, &PAUSE_OPTIONS, &layout, &mut self.input);
+        self.status.mode = GameMode::Pause {
+            selection,
+            settings: None,
+        };
+        match event {
+            Some(MenuEvent::Back) | Some(MenuEvent::Confirm(0)) => {
+                self.status.mode = GameMode::Play;
+            }
+            Some(MenuEvent::Confirm(1)) => {
+                self.status.mode = GameMode::Pause {
+                    selection,
+                    settings: Some(SettingsScreen::default()),
+                };
+            }
+            Some(MenuEvent::Confirm(2)) => {
+                match self.save() {
+                    Ok(path) => info!("Saved to {}", path.display()),
+                    Err(e) => error!("Failed to save: {e}"),
+                }
+                save::refresh_saves();
+                self.audio.stop_music();
+                self.status.mode = GameMode::Title {
+                    phase: TitlePhase::Start,
+                };
+            }
+            Some(MenuEvent::Confirm(_)) => miniquad::window::order_quit(),
+            None => {}
         }
     }
-    pub fn set_debug_tx(&mut self, sender: Arc<UnboundedSender<String>>) -> Nresult {
-        self.debug = Some(sender);
-        Ok(())
-    }
-    pub fn call_render_dbg(&mut self, rx: &mut UnboundedReceiver<String>) -> Nresult {
-        self.renderer.render_debug(rx)
+    /// Writes the current run to a new file in `SAVE_LOC`.
+    pub fn save(&self) -> Result<std::path::PathBuf> {
+        self.save_data().write_new(Path::new(SAVE_LOC))
     }
-    pub fn log(&mut self, s: &str) -> Nresult {
-        if let Some(tx) = &self.debug {
-            tx.unbounded_send(s.into())
-                .map_err(|el| crate::GameError::Misc(Box::new(el)))?;
+    /// The run in progress, as it would be saved.
+    fn save_data(&self) -> SaveData {
+        SaveData {
+            map: self.world.map,
+            level: self.status.run.level,
+            player_pos: self.world.player_pos.into(),
+            health: self.player.health,
+            lives: self.status.lives,
+            time: self.status.run.time,
+            damage_dealt: self.status.run.damage_dealt,
+            defeated: DEFEATED.load(std::sync::atomic::Ordering::Relaxed),
         }
-        Ok(())
+    }
+    /// Continues the run stored in `data`. Call after `reset_run`.
+    fn apply_save(&mut self, data: &SaveData) {
+        self.world.map = data.map;
+        self.world.player_pos = data.player_pos.into();
+        self.player.health = data.health;
+        self.status.lives = data.lives;
+        self.status.run = RunStats {
+            time: data.time,
+            damage_dealt: data.damage_dealt,
+            level: if data.level == 0 {
+                data.map + 1
+            } else {
+                data.level
+            },
+        };
+        DEFEATED.store(data.defeated, std::sync::atomic::Ordering::Release);
+        let map_size = self.world.map_size(&self.assets);
+        self.renderer.camera.snap(self.world.player_pos, map_size);
     }
 }
 
@@ -306,4 +981,77 @@ pub struct Status {
     pub mode: GameMode,
     pub health: u32,
     pub lives: u32,
+    pub run: RunStats,
+    /// Name being typed while renaming a save.
+    pub rename: String,
+}
+
+/// Statistics of the current run, shown on the game over screen. Kills are in `DEFEATED`.
+#[derive(Default)]
+pub struct RunStats {
+    /// Seconds spent alive in `GameMode::Play`.
+    pub time: f32,
+    pub damage_dealt: f32,
+    pub level: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audio::NullAudio,
+        input::{actions::ActionMap, gamepad::NullGamepad},
+        util::FixedClock,
+    };
+
+    const DT: f32 = 1. / 60.;
+
+    pub(super) fn headless() -> GameModel {
+        let input = InputMan::with_gamepad(Box::new(NullGamepad), ActionMap::default());
+        GameModel::new(
+            Assets::stub(),
+            Settings::default(),
+            Box::new(NullAudio),
+            input,
+            Box::new(FixedClock { dt: DT }),
+        )
+    }
+
+    #[test]
+    fn runs_without_a_window() {
+        let mut model = headless();
+        model.update();
+        assert!(matches!(model.status.mode, GameMode::Title { .. }));
+
+        model.reset_run();
+        model.start_level();
+        model.status.mode = GameMode::Play;
+        for _ in 0..120 {
+            model.update();
+        }
+        assert!(matches!(model.status.mode, GameMode::Play));
+        assert!((model.status.run.time - 120. * DT).abs() < 1e-3);
+        assert!(model.player.health > 0.);
+    }
+
+    #[test]
+    fn cursor_hides_only_in_play() {
+        let mut model = headless();
+        assert!(model.cursor_visible());
+        model.status.mode = GameMode::Play;
+        assert!(!model.cursor_visible());
+        for mode in [
+            GameMode::Pause {
+                selection: 0,
+                settings: None,
+            },
+            GameMode::GameOver { selection: 0 },
+            GameMode::Title {
+                phase: TitlePhase::Settings(SettingsScreen::default()),
+            },
+        ] {
+            model.status.mode = mode;
+            assert!(model.cursor_visible(), "{mode:?}");
+        }
+    }
 }
@@ -1,6 +1,57 @@
-use std::f32::consts::PI;
+use macroquad::prelude::*;
 
-use super::{damage::Damageable, weapons::Weapon};
+use super::{
+    animation::{AnimEvent, AnimState, Animator, Clips},
+    damage::Damageable,
+    weapons::{Weapon, weaponmap::WeaponMap},
+};
+use crate::util::angle_dir;
+
+/// How quickly the player speeds up and slows down, and how they dash.
+#[derive(Debug, Clone, Copy)]
+pub struct Movement {
+    /// Speed gained per second while moving, in world units per second squared.
+    pub accel: f32,
+    /// Speed lost per second while stopping or turning.
+    pub decel: f32,
+    /// `None` disables dashing.
+    pub dash: Option<DashStats>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DashStats {
+    pub speed: f32,
+    /// Seconds the dash lasts.
+    pub duration: f32,
+    /// Seconds from the start of one dash until the next is allowed.
+    pub cooldown: f32,
+    /// Seconds of invulnerability from the start of the dash.
+    pub iframes: f32,
+}
+
+impl Default for Movement {
+    fn default() -> Self {
+        Movement {
+            accel: 3000.,
+            decel: 4000.,
+            dash: Some(DashStats {
+                speed: 1200.,
+                duration: 0.15,
+                cooldown: 1.,
+                iframes: 0.25,
+            }),
+        }
+    }
+}
+
+/// Timers of the dash in progress, all counting down to 0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DashState {
+    pub timer: f32,
+    pub cooldown: f32,
+    pub iframes: f32,
+    pub dir: Vec2,
+}
 
 pub struct Player {
     pub max_health: f32,
@@ -10,6 +61,15 @@ pub struct Player {
     pub speed: f32,
     pub weapon: Weapon,
     pub stun: f32,
+    /// Movement this frame, in world units per second.
+    pub velocity: Vec2,
+    /// Aim direction, as returned by `InputMan::aim`.
+    pub aim: f32,
+    pub anim: Animator,
+    pub movement: Movement,
+    pub dash: DashState,
+    /// Ignores all damage. Set with the console's `god` command.
+    pub god: bool,
 }
 
 impl Player {
@@ -29,35 +89,90 @@ impl Player {
             speed,
             weapon,
             stun,
+            velocity: Vec2::ZERO,
+            aim: 0.,
+            anim: Animator::new(),
+            movement: Movement::default(),
+            dash: DashState::default(),
+            god: false,
         }
     }
-}
-impl Default for Player {
-    fn default() -> Self {
-        Self {
-            max_health: 100.,
-            health: 100.,
-            attack: 5.,
-            defense: 5.,
-            speed: 300.,
-            weapon: Weapon {
-                power: 10.,
-                crit_chance: 0.1,
-                cooldown: 0.3,
-                cooldown_counter: 0.,
-                knockback: 0.,
-                stun: 0.,
-                animation: 0.,
-                kind: super::weapons::WeaponKind::Melee {
-                    range: 250.,
-                    angle: PI / 3.,
-                },
-            },
-            stun: 0.,
+    /// Fresh stats and weapon 0, as at the start of a run.
+    pub fn starting(weapons: &WeaponMap) -> Self {
+        Self::new(100., 5., 5., 300., Weapon::new(0, weapons), 0.)
+    }
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0.
+    }
+    /// Sprites face right, so the player is flipped whenever aiming to the left.
+    pub fn facing_left(&self) -> bool {
+        self.aim.cos() < 0.
+    }
+    /// Whether damage is currently ignored, e.g. while dashing.
+    pub fn invulnerable(&self) -> bool {
+        self.god || self.dash.iframes > 0.
+    }
+    pub fn dashing(&self) -> bool {
+        self.dash.timer > 0.
+    }
+    /// Starts a dash towards `input`, or towards the aim when standing still.
+    /// Does nothing while dashing is disabled or on cooldown.
+    pub fn dash(&mut self, input: Vec2) {
+        let Some(stats) = self.movement.dash else {
+            return;
+        };
+        if self.dash.cooldown > 0. {
+            return;
+        }
+        let dir = if input != Vec2::ZERO {
+            input.normalize()
+        } else {
+            angle_dir(self.aim)
+        };
+        self.dash = DashState {
+            timer: stats.duration,
+            cooldown: stats.cooldown,
+            iframes: stats.iframes,
+            dir,
+        };
+    }
+    /// Accelerates towards `input * speed`. `input` is clamped to length 1, so diagonals
+    /// are no faster than straight lines but a half-tilted stick still walks slower.
+    pub fn steer(&mut self, input: Vec2, dt: f32) {
+        self.dash.timer = (self.dash.timer - dt).max(0.);
+        self.dash.cooldown = (self.dash.cooldown - dt).max(0.);
+        self.dash.iframes = (self.dash.iframes - dt).max(0.);
+        if self.dashing()
+            && let Some(stats) = self.movement.dash
+        {
+            self.velocity = self.dash.dir * stats.speed;
+            return;
         }
+        let target = input.clamp_length_max(1.) * self.speed;
+        // speeding up along the current direction uses accel, anything else counts as braking.
+        let rate = if target.dot(self.velocity) >= 0. && target.length() > self.velocity.length() {
+            self.movement.accel
+        } else {
+            self.movement.decel
+        };
+        self.velocity += (target - self.velocity).clamp_length_max(rate * dt);
+    }
+    /// Picks the clip from health, damage and movement, and advances it. Returns the events of
+    /// the frames entered.
+    pub fn update_animation<T>(&mut self, clips: &Clips<T>, dt: f32) -> Vec<AnimEvent> {
+        let state = if self.is_dead() {
+            AnimState::Death
+        } else if self.anim.state == AnimState::Hurt && !self.anim.finished(clips) {
+            AnimState::Hurt
+        } else if self.velocity != Vec2::ZERO {
+            AnimState::Walk
+        } else {
+            AnimState::Idle
+        };
+        self.anim.set_state(state);
+        self.anim.update(clips, dt)
     }
 }
-
 impl Damageable for Player {
     fn take_damage(
         &mut self,
@@ -65,7 +180,7 @@ impl Damageable for Player {
         effects: Vec<super::damage::Effects>,
     ) -> crate::errors::Nresult {
         if self.stun <= 0. {
-            self.take_damage_raw(damage.evaluate()?)?;
+            self.take_damage_raw(damage.evaluate()?.amount)?;
             self.stun = damage.stun;
             Ok(())
         } else {
@@ -73,7 +188,13 @@ impl Damageable for Player {
         }
     }
     fn take_damage_raw(&mut self, damage: f32) -> crate::errors::Nresult {
+        if self.invulnerable() {
+            return Ok(());
+        }
         self.health -= damage;
+        if damage > 0. && !self.is_dead() {
+            self.anim.set_state(AnimState::Hurt);
+        }
         Ok(())
     }
 }
@@ -0,0 +1,217 @@
+use std::{
+    fs::File,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::UNIX_EPOCH,
+};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{GameError, Nresult, Result},
+    util::unix_now,
+};
+
+use super::SAVE_LOC;
+
+const SAVE_EXT: &str = "json";
+
+/// A run in progress, as written to `SAVE_LOC` by "Save & Quit".
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveData {
+    pub map: u32,
+    #[serde(default)]
+    pub level: u32,
+    pub player_pos: [f32; 2],
+    pub health: f32,
+    pub lives: u32,
+    pub time: f32,
+    pub damage_dealt: f32,
+    pub defeated: u32,
+}
+
+impl SaveData {
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|_| GameError::NotFoundError(path.to_path_buf()))?;
+        serde_json::from_reader(file)
+            .map_err(|e| GameError::InvalidSave(format!("{}: {e}", path.display())))
+    }
+    /// Writes to a new file in `dir`, named after the current time. Saves made in the same second
+    /// get a counter appended, so none is overwritten. Returns its path.
+    pub fn write_new(&self, dir: &Path) -> Result<PathBuf> {
+        let secs = unix_now().as_secs();
+        let mut n = 1;
+        let (path, file) = loop {
+            let name = match n {
+                1 => format!("run-{secs}.{SAVE_EXT}"),
+                n => format!("run-{secs}-{n}.{SAVE_EXT}"),
+            };
+            let path = dir.join(name);
+            match File::create_new(&path) {
+                Ok(file) => break (path, file),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| GameError::Unexpected(format!("{}: {e}", path.display())))?;
+        Ok(path)
+    }
+}
+
+/// One file in `SAVE_LOC`, with what the save browser shows about it.
+#[derive(Clone)]
+pub struct SaveEntry {
+    pub path: PathBuf,
+    /// File name without extension.
+    pub name: String,
+    /// Last write, in seconds since the unix epoch.
+    pub modified: Option<u64>,
+    /// `None` if the file couldn't be read as a save.
+    pub data: Option<SaveData>,
+}
+
+impl SaveEntry {
+    fn read(path: PathBuf) -> Self {
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |el| el.to_string_lossy().into_owned());
+        let modified = path
+            .metadata()
+            .and_then(|el| el.modified())
+            .ok()
+            .and_then(|el| el.duration_since(UNIX_EPOCH).ok())
+            .map(|el| el.as_secs());
+        let data = SaveData::load(&path).ok();
+        SaveEntry {
+            path,
+            name,
+            modified,
+            data,
+        }
+    }
+}
+
+/// Saves in `SAVE_LOC`, newest first. Call `refresh_saves` after touching the directory.
+pub static SAVE_CACHE: Lazy<RwLock<Vec<SaveEntry>>> = Lazy::new(|| RwLock::new(scan_saves()));
+
+fn scan_saves() -> Vec<SaveEntry> {
+    let Ok(dir) = Path::new(SAVE_LOC).read_dir() else {
+        return Vec::new();
+    };
+    let mut saves: Vec<_> = dir
+        .filter_map(|el| el.ok())
+        .map(|el| el.path())
+        .filter(|el| el.is_file())
+        .map(SaveEntry::read)
+        .collect();
+    saves.sort_by(|a, b| {
+        b.modified
+            .cmp(&a.modified)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    saves
+}
+
+pub fn refresh_saves() {
+    *SAVE_CACHE.write().unwrap() = scan_saves();
+}
+
+pub fn delete_save(path: &Path) -> Nresult {
+    std::fs::remove_file(path)?;
+    refresh_saves();
+    Ok(())
+}
+
+/// Renames the save at `path` to `name`. Names are limited to letters, digits, ` `, `-` and `_`.
+pub fn rename_save(path: &Path, name: &str) -> Nresult {
+    let name = name.trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|el| el.is_alphanumeric() || matches!(el, ' ' | '-' | '_'))
+    {
+        return Err(GameError::IllegalArgument(format!(
+            "invalid save name {name:?}"
+        )));
+    }
+    let target = path.with_file_name(format!("{name}.{SAVE_EXT}"));
+    if target == path {
+        return Ok(());
+    }
+    if target.exists() {
+        return Err(GameError::IllegalArgument(format!(
+            "a save called {name:?} already exists"
+        )));
+    }
+    std::fs::rename(path, target)?;
+    refresh_saves();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pixel_rebels-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn data(map: u32) -> SaveData {
+        SaveData {
+            map,
+            level: map + 1,
+            player_pos: [1., 2.],
+            health: 50.,
+            lives: 2,
+            time: 12.5,
+            damage_dealt: 100.,
+            defeated: 3,
+        }
+    }
+
+    #[test]
+    fn saves_in_the_same_second_get_their_own_files() {
+        let dir = temp_dir("write_new");
+        let paths: Vec<_> = (0..5).map(|i| data(i).write_new(&dir).unwrap()).collect();
+        for (i, path) in paths.iter().enumerate() {
+            assert_eq!(SaveData::load(path).unwrap().map, i as u32);
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn broken_saves_are_invalid_saves() {
+        let dir = temp_dir("load");
+        let path = dir.join(format!("broken.{SAVE_EXT}"));
+        std::fs::write(&path, "{\"map\": 1").unwrap();
+        assert!(matches!(
+            SaveData::load(&path),
+            Err(GameError::InvalidSave(_))
+        ));
+        assert!(matches!(
+            SaveData::load(&dir.join("missing")),
+            Err(GameError::NotFoundError(_))
+        ));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn renaming_to_the_current_name_is_a_no_op() {
+        let dir = temp_dir("rename");
+        let path = dir.join(format!("mine.{SAVE_EXT}"));
+        let other = dir.join(format!("other.{SAVE_EXT}"));
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::write(&other, "{}").unwrap();
+        assert!(rename_save(&path, " mine ").is_ok());
+        assert!(path.exists());
+        assert!(rename_save(&path, "other").is_err());
+        assert!(rename_save(&path, "bad/name").is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,21 +1,41 @@
-use std::{f32::consts::PI, ops::Deref};
+use std::ops::Deref;
 
 use crate::{
-    errors::Nresult,
-    util::{self, get_mouse_angle, get_mouse_angle_centered},
+    errors::Result,
+    util::{angle_diff, screen_angle},
 };
 
-use super::{World, damage::Damageable};
+use super::{
+    World,
+    damage::{Damage, Damageable},
+};
+use weaponmap::{WeaponMap, WeaponStats};
 
 use macroquad::prelude::*;
 use rayon::iter::{
     IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
 };
+use serde::Deserialize;
 
 pub mod bullets;
 pub mod weaponmap;
 
+/// What one call to `Weapon::attack` did.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AttackOutcome {
+    /// False while the weapon was still cooling down.
+    pub swung: bool,
+    /// Enemies hit.
+    pub hits: u32,
+    /// Damage dealt, not counting overkill.
+    pub dealt: f32,
+    /// Whether the swing rolled a crit. Every enemy it hits takes the crit damage.
+    pub crit: bool,
+}
+
 pub struct Weapon {
+    /// Index into `weaponmap`.
+    pub id: u32,
     pub power: f32,
     pub crit_chance: f32,
     pub cooldown: f32,
@@ -27,15 +47,43 @@ pub struct Weapon {
 }
 
 impl Weapon {
-    pub fn attack(&mut self, world: &mut World) -> Nresult {
-        let mangle = get_mouse_angle_centered(world) + PI;
+    pub fn new(id: u32, weapons: &WeaponMap) -> Self {
+        let mut weapon = Weapon {
+            id,
+            power: 0.,
+            crit_chance: 0.,
+            cooldown: 0.,
+            cooldown_counter: 0.,
+            knockback: 0.,
+            stun: 0.,
+            animation: 0.,
+            kind: WeaponKind::Melee {
+                range: 0.,
+                angle: 0.,
+            },
+        };
+        weapon.apply_stats(&weapons.get(id));
+        weapon
+    }
+    /// Overwrites the stats, keeping cooldown and animation progress.
+    pub fn apply_stats(&mut self, stats: &WeaponStats) {
+        self.power = stats.power;
+        self.crit_chance = stats.crit_chance;
+        self.cooldown = stats.cooldown;
+        self.knockback = stats.knockback;
+        self.stun = stats.stun;
+        self.kind = stats.kind;
+    }
+    /// Attacks towards `mangle`, an aim angle as returned by `InputMan::aim`.
+    pub fn attack(&mut self, world: &mut World, mangle: f32) -> Result<AttackOutcome> {
         match self.kind {
             WeaponKind::Melee { range, .. } => {
                 if self.cooldown_counter > 0. {
-                    return Ok(());
+                    return Ok(AttackOutcome::default());
                 } else {
                     self.cooldown_counter = self.cooldown;
                 }
+                let hit = Damage::swing(self).evaluate()?;
                 let find_y_center = world.player_pos.y;
                 let bottom = world
                     .horde
@@ -54,50 +102,90 @@ impl Weapon {
                     } else {
                         top_idx = world.horde.len();
                     }
-                    world.horde[found..top_idx]
+                    let (hits, dealt) = world.horde[found..top_idx]
                         .par_iter_mut()
+                        .filter(|el| el.health > 0.)
                         .filter(|el| self.kind.collides(world.player_pos, el.loc, mangle))
-                        .for_each(|el| {
-                            let _ = el.take_damage_raw(self.power);
-                        });
-                    Ok(())
+                        .map(|el| {
+                            let dealt = hit.amount.min(el.health);
+                            let _ = el.take_damage_raw(hit.amount);
+                            (1, dealt)
+                        })
+                        .reduce(|| (0, 0.), |a, b| (a.0 + b.0, a.1 + b.1));
+                    Ok(AttackOutcome {
+                        swung: true,
+                        hits,
+                        dealt,
+                        crit: hit.crit,
+                    })
                 } else {
-                    Ok(())
+                    Ok(AttackOutcome {
+                        swung: true,
+                        crit: hit.crit,
+                        ..Default::default()
+                    })
                 }
             }
-            _ => Ok(()),
+            _ => Ok(AttackOutcome::default()),
         }
     }
-    pub fn adjust_cooldown(&mut self) {
+    pub fn adjust_cooldown(&mut self, dt: f32) {
         if self.cooldown_counter > 0. {
-            self.cooldown_counter -= get_frame_time();
+            self.cooldown_counter -= dt;
         }
     }
 }
 
+#[derive(Deserialize, Clone, Copy)]
 pub enum WeaponKind {
     Projectile { bullet: u32, speed: f32 },
     Melee { range: f32, angle: f32 },
 }
 
 impl WeaponKind {
-    /// checks collision of weapon with origin `origin`, and target `target` both `Vec2`s. angle is
-    /// in f32.
-
+    /// checks collision of weapon with origin `origin`, and target `target` both `Vec2`s.
+    /// `center_angle` is the aim direction as returned by `util::screen_angle`.
     pub fn collides(&self, origin: Vec2, target: Vec2, center_angle: f32) -> bool {
         match self {
             Self::Melee { range, angle } => {
                 if origin.distance(target) > *range {
                     false
                 } else {
-                    let delta = target - origin;
-                    let enemy_angle = PI - delta.to_angle();
-                    let delta_angle = enemy_angle - center_angle;
-                    delta_angle.abs() % (2. * PI) <= *angle / 2.
-                        || (delta_angle + 2. * PI).abs() <= angle / 2.
+                    let enemy_angle = screen_angle(target - origin);
+                    angle_diff(enemy_angle, center_angle).abs() <= *angle / 2.
                 }
             }
             _ => false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{angle_dir, normalize_angle};
+    use std::f32::consts::PI;
+
+    /// Points just inside and just outside the edges `draw_weapon` draws hit or miss accordingly.
+    #[test]
+    fn hit_cone_matches_drawn_arc() {
+        let origin = vec2(37., -12.);
+        for angle in [0.3, PI / 2., 2.5, 2. * PI - 0.2] {
+            let kind = WeaponKind::Melee { range: 80., angle };
+            for i in 0..720 {
+                let aim = normalize_angle(i as f32 * PI / 360.);
+                for side in [-1., 1.] {
+                    let edge = aim + side * angle / 2.;
+                    let inside = edge - side * 0.01;
+                    let outside = edge + side * 0.01;
+                    for dist in [1., 40., 79.] {
+                        let at = |a: f32| origin + angle_dir(a) * dist;
+                        assert!(kind.collides(origin, at(inside), aim), "{aim} {angle}");
+                        assert!(!kind.collides(origin, at(outside), aim), "{aim} {angle}");
+                    }
+                    assert!(!kind.collides(origin, origin + angle_dir(aim) * 81., aim));
+                }
+            }
+        }
+    }
+}
@@ -1 +1,81 @@
-pub struct WeaponStats {}
+use std::{f32::consts::PI, fs::File, path::PathBuf};
+
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+use super::WeaponKind;
+use crate::{
+    assets::ASSET_LOC,
+    errors::{GameError, Result},
+};
+
+pub const WEAPON_DATA_LOC: &str = "weapons.json";
+
+/// Stats of one weapon. `assets/weapons.json` holds one per weapon id.
+#[derive(Deserialize, Clone, Copy)]
+pub struct WeaponStats {
+    pub power: f32,
+    pub crit_chance: f32,
+    pub cooldown: f32,
+    pub knockback: f32,
+    pub stun: f32,
+    pub kind: WeaponKind,
+}
+
+impl Default for WeaponStats {
+    /// The starting sword, used when the weapon data can't be read.
+    fn default() -> Self {
+        WeaponStats {
+            power: 10.,
+            crit_chance: 0.1,
+            cooldown: 0.3,
+            knockback: 0.,
+            stun: 0.,
+            kind: WeaponKind::Melee {
+                range: 250.,
+                angle: PI / 3.,
+            },
+        }
+    }
+}
+
+/// Every weapon, indexed by weapon id. Part of `assets::Assets`.
+pub struct WeaponMap {
+    map: Vec<WeaponStats>,
+}
+
+impl WeaponMap {
+    /// Stats of weapon `id`, or the default weapon if there is no such id.
+    pub fn get(&self, id: u32) -> WeaponStats {
+        self.map.get(id as usize).copied().unwrap_or_default()
+    }
+
+    /// How many weapon ids `assets/weapons.json` defines.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Falls back to no weapons at all if the stats can't be read, so every id is the default.
+    pub fn load() -> Self {
+        let map = read_weapons().unwrap_or_else(|e| {
+            error!("Failed to load weapons: {e}");
+            Vec::new()
+        });
+        WeaponMap { map }
+    }
+
+    /// Just the default weapon, without touching the disk.
+    #[cfg(test)]
+    pub fn stub() -> Self {
+        WeaponMap {
+            map: vec![WeaponStats::default()],
+        }
+    }
+}
+
+fn read_weapons() -> Result<Vec<WeaponStats>> {
+    let path = PathBuf::from(ASSET_LOC).join(WEAPON_DATA_LOC);
+    let file = File::open(&path).map_err(|_| GameError::NotFoundError(path.clone()))?;
+    serde_json::from_reader(file)
+        .map_err(|e| GameError::AssetLoadFailure(format!("{}: {e}", path.display())))
+}
@@ -1,5 +1,5 @@
 use super::*;
-use crate::assets::get_map;
+use crate::assets::Assets;
 
 pub struct World {
     pub player_pos: Vec2,
@@ -15,7 +15,7 @@ impl World {
             map: 0,
         }
     }
-    pub fn map_size(&self) -> Vec2 {
-        get_map(self.map).unwrap().size()
+    pub fn map_size(&self, assets: &Assets) -> Vec2 {
+        assets.map(self.map).map_or(Vec2::ZERO, |el| el.size)
     }
 }
@@ -0,0 +1,263 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use crate::{
+    errors::Result,
+    model::{GameModel, console::Command},
+    util::unix_now,
+};
+
+pub const PROFILE_LOC: &str = "profiles/";
+/// Frames each phase's statistics are computed over.
+pub const PROFILE_WINDOW: usize = 300;
+
+static PROFILER: Mutex<Profiler> = Mutex::new(Profiler::new());
+
+/// Rolling per-frame timings of named phases.
+struct Profiler {
+    /// Phases in the order they were first timed.
+    phases: Vec<Phase>,
+}
+
+struct Phase {
+    name: &'static str,
+    /// Time spent this frame so far, in milliseconds. A phase may be timed more than once.
+    current: Option<f32>,
+    /// Milliseconds per frame, oldest first. Frames the phase didn't run in are left out.
+    history: VecDeque<f32>,
+}
+
+impl Profiler {
+    const fn new() -> Self {
+        Profiler { phases: Vec::new() }
+    }
+    fn record(&mut self, name: &'static str, ms: f32) {
+        let phase = match self.phases.iter().position(|el| el.name == name) {
+            Some(i) => &mut self.phases[i],
+            None => {
+                self.phases.push(Phase {
+                    name,
+                    current: None,
+                    history: VecDeque::with_capacity(PROFILE_WINDOW),
+                });
+                self.phases.last_mut().unwrap()
+            }
+        };
+        *phase.current.get_or_insert(0.) += ms;
+    }
+}
+
+/// Statistics of one phase over the last `PROFILE_WINDOW` frames, in milliseconds.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    pub samples: usize,
+    pub avg: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Stats {
+    fn new(history: &VecDeque<f32>) -> Self {
+        if history.is_empty() {
+            return Stats::default();
+        }
+        let mut sorted: Vec<f32> = history.iter().copied().collect();
+        sorted.sort_by(f32::total_cmp);
+        // nearest-rank percentile.
+        let percentile = |p: f32| sorted[((p * sorted.len() as f32).ceil() as usize).max(1) - 1];
+        Stats {
+            samples: sorted.len(),
+            avg: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Times the rest of the enclosing block as phase `name`. Bind it to a named variable,
+/// `let _scope = ...`, since `let _ = ...` drops it right away.
+pub struct Scope {
+    name: &'static str,
+    start: Instant,
+}
+
+pub fn scope(name: &'static str) -> Scope {
+    Scope {
+        name,
+        start: Instant::now(),
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let ms = self.start.elapsed().as_secs_f32() * 1000.;
+        if let Ok(mut profiler) = PROFILER.lock() {
+            profiler.record(self.name, ms);
+        }
+    }
+}
+
+/// Runs `f`, timing it as phase `name`.
+pub fn time<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    let _scope = scope(name);
+    f()
+}
+
+/// Moves this frame's timings into the rolling windows. Call once per frame.
+pub fn end_frame() {
+    let Ok(mut profiler) = PROFILER.lock() else {
+        return;
+    };
+    for phase in &mut profiler.phases {
+        if let Some(ms) = phase.current.take() {
+            if phase.history.len() == PROFILE_WINDOW {
+                phase.history.pop_front();
+            }
+            phase.history.push_back(ms);
+        }
+    }
+}
+
+/// Statistics of every phase, in the order they were first timed.
+pub fn stats() -> Vec<(&'static str, Stats)> {
+    PROFILER.lock().map_or_else(
+        |_| Vec::new(),
+        |profiler| {
+            profiler
+                .phases
+                .iter()
+                .map(|el| (el.name, Stats::new(&el.history)))
+                .collect()
+        },
+    )
+}
+
+pub fn reset() {
+    if let Ok(mut profiler) = PROFILER.lock() {
+        profiler.phases.clear();
+    }
+}
+
+/// Writes the current statistics to `PROFILE_LOC/profile-<unix time>.csv`. Returns its path.
+pub fn dump_csv() -> Result<PathBuf> {
+    let secs = unix_now().as_secs();
+    fs::create_dir_all(PROFILE_LOC)?;
+    let path = Path::new(PROFILE_LOC).join(format!("profile-{secs}.csv"));
+    let mut file = File::create(&path)?;
+    writeln!(file, "phase,samples,avg_ms,p50_ms,p95_ms,p99_ms,max_ms")?;
+    for (name, el) in stats() {
+        writeln!(
+            file,
+            "{name},{},{:.4},{:.4},{:.4},{:.4},{:.4}",
+            el.samples, el.avg, el.p50, el.p95, el.p99, el.max
+        )?;
+    }
+    Ok(path)
+}
+
+/// Console commands for the profiler, registered by `GameModel::new`.
+pub fn commands() -> [Command; 2] {
+    [
+        Command {
+            name: "profile_dump",
+            usage: "",
+            help: "writes phase timings to a csv file",
+            run: cmd_profile_dump,
+            complete: None,
+        },
+        Command {
+            name: "profile_reset",
+            usage: "",
+            help: "forgets all phase timings",
+            run: cmd_profile_reset,
+            complete: None,
+        },
+    ]
+}
+
+fn cmd_profile_dump(model: &mut GameModel, _args: &[&str]) -> Result<String> {
+    let path = dump_csv()?;
+    Ok(format!(
+        "wrote {} with {} enemies alive",
+        path.display(),
+        model.world.horde.len()
+    ))
+}
+
+fn cmd_profile_reset(_model: &mut GameModel, _args: &[&str]) -> Result<String> {
+    reset();
+    Ok("profiler reset".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stats of `1..=n` milliseconds, given in reverse so sorting is exercised too.
+    fn stats_up_to(n: u32) -> Stats {
+        Stats::new(&(1..=n).rev().map(|el| el as f32).collect())
+    }
+
+    #[test]
+    fn no_samples_give_zeros() {
+        let stats = Stats::new(&VecDeque::new());
+        assert_eq!(stats.samples, 0);
+        assert_eq!(stats.max, 0.);
+    }
+
+    #[test]
+    fn one_sample_is_every_percentile() {
+        let stats = Stats::new(&VecDeque::from([4.5]));
+        assert_eq!(stats.samples, 1);
+        for value in [stats.avg, stats.p50, stats.p95, stats.p99, stats.max] {
+            assert_eq!(value, 4.5);
+        }
+    }
+
+    #[test]
+    fn even_counts_take_the_lower_middle() {
+        let stats = stats_up_to(4);
+        assert_eq!(stats.avg, 2.5);
+        // nearest rank doesn't interpolate, the median of 1..=4 is 2.
+        assert_eq!(stats.p50, 2.);
+        assert_eq!(stats.p95, 4.);
+        assert_eq!(stats.max, 4.);
+    }
+
+    #[test]
+    fn p99_of_small_sets_is_the_max() {
+        for n in [2, 10, 50, 99] {
+            let stats = stats_up_to(n);
+            assert_eq!(stats.p99, n as f32, "{n} samples");
+        }
+    }
+
+    #[test]
+    fn percentiles_land_on_their_rank() {
+        let stats = stats_up_to(100);
+        assert_eq!(stats.p50, 50.);
+        assert_eq!(stats.p95, 95.);
+        assert_eq!(stats.p99, 99.);
+        assert_eq!(stats.max, 100.);
+        // the exact rank is `ceil(percent * n / 100)`, float rounding mustn't move it.
+        for n in 1..=1000 {
+            let stats = stats_up_to(n);
+            let rank = |percent: u32| (percent * n).div_ceil(100) as f32;
+            assert_eq!(
+                (stats.p50, stats.p95, stats.p99),
+                (rank(50), rank(95), rank(99)),
+                "{n} samples"
+            );
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use macroquad::prelude::*;
+
+/// World units² visible at zoom 1, whatever the window shape. Matches the old 1920x1080 view.
+const VIEW_AREA: f32 = 2000. * 1125.;
+/// How fast the camera catches up with its target. Higher is snappier.
+const FOLLOW_SPEED: f32 = 8.;
+/// Trauma lost per second.
+const SHAKE_DECAY: f32 = 1.5;
+/// Offset in world units at full trauma.
+const MAX_SHAKE: f32 = 12.;
+
+pub const MIN_ZOOM: f32 = 0.5;
+pub const MAX_ZOOM: f32 = 3.;
+/// Zoom multiplier per mouse wheel notch.
+pub const ZOOM_STEP: f32 = 1.1;
+/// Virtual resolution used when letterboxing is turned on.
+pub const DEFAULT_RESOLUTION: Vec2 = vec2(1920., 1080.);
+
+/// The view into the world. Every world-space draw call and the mouse aim go through this.
+pub struct Camera {
+    /// World position at the center of the screen, before shake.
+    pub pos: Vec2,
+    pub zoom: f32,
+    /// Fixed virtual resolution. When set, the world is letterboxed to its aspect ratio.
+    pub resolution: Option<Vec2>,
+    /// Window size in pixels. The main loop keeps it current, so the camera works without a window.
+    pub screen: Vec2,
+    map_size: Vec2,
+    trauma: f32,
+    shake_offset: Vec2,
+    /// Kept apart from macroquad's global generator, so shaking doesn't change what a seed spawns.
+    shake_rng: rand::RandGenerator,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Camera {
+            pos: Vec2::ZERO,
+            zoom: 1.,
+            resolution: None,
+            screen: DEFAULT_RESOLUTION,
+            map_size: Vec2::ZERO,
+            trauma: 0.,
+            shake_offset: Vec2::ZERO,
+            shake_rng: rand::RandGenerator::new(),
+        }
+    }
+
+    /// Part of the window the world is drawn into, in screen pixels.
+    pub fn viewport(&self) -> Rect {
+        self.viewport_in(self.screen)
+    }
+
+    /// `viewport` for a window of `screen` pixels.
+    fn viewport_in(&self, screen: Vec2) -> Rect {
+        match self.resolution {
+            None => Rect::new(0., 0., screen.x, screen.y),
+            Some(res) => {
+                let size = res * (screen / res).min_element();
+                let offset = (screen - size) / 2.;
+                Rect::new(offset.x, offset.y, size.x, size.y)
+            }
+        }
+    }
+
+    /// Screen pixels per world unit.
+    pub fn scale(&self) -> f32 {
+        self.scale_in(self.viewport())
+    }
+
+    fn scale_in(&self, viewport: Rect) -> f32 {
+        (viewport.w * viewport.h / VIEW_AREA).sqrt() * self.zoom
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+
+    /// Zooms by `notches` mouse wheel steps. Positive zooms in.
+    pub fn zoom_by(&mut self, notches: f32) {
+        self.set_zoom(self.zoom * ZOOM_STEP.powf(notches));
+    }
+
+    pub fn toggle_letterbox(&mut self) {
+        self.resolution = match self.resolution {
+            Some(_) => None,
+            None => Some(DEFAULT_RESOLUTION),
+        };
+    }
+
+    /// Adds trauma in `0..=1`. Shake strength is trauma squared, so small hits stay subtle.
+    pub fn shake(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).clamp(0., 1.);
+    }
+
+    /// Eases towards `target`, keeping the view inside a map of `map_size`.
+    pub fn follow(&mut self, target: Vec2, map_size: Vec2, dt: f32) {
+        self.map_size = map_size;
+        let t = 1. - (-FOLLOW_SPEED * dt).exp();
+        self.pos = self.clamp(self.pos.lerp(target, t));
+
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.);
+        let strength = self.trauma * self.trauma * MAX_SHAKE;
+        self.shake_offset = vec2(
+            self.shake_rng.gen_range(-1., 1.) * strength,
+            self.shake_rng.gen_range(-1., 1.) * strength,
+        );
+    }
+
+    /// Jumps straight to `target` without easing.
+    pub fn snap(&mut self, target: Vec2, map_size: Vec2) {
+        self.map_size = map_size;
+        self.pos = self.clamp(target);
+    }
+
+    /// Keeps the view inside the map. Axes where the map is smaller than the view are centered.
+    fn clamp(&self, pos: Vec2) -> Vec2 {
+        let half_view = self.viewport().size() / 2. / self.scale();
+        let clamp_axis = |pos: f32, map: f32, half: f32| {
+            if map <= half * 2. {
+                map / 2.
+            } else {
+                pos.clamp(half, map - half)
+            }
+        };
+        vec2(
+            clamp_axis(pos.x, self.map_size.x, half_view.x),
+            clamp_axis(pos.y, self.map_size.y, half_view.y),
+        )
+    }
+
+    fn center(&self) -> Vec2 {
+        self.pos + self.shake_offset
+    }
+
+    pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+        self.world_to_screen_in(world, self.viewport())
+    }
+
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        self.screen_to_world_in(screen, self.viewport())
+    }
+
+    fn world_to_screen_in(&self, world: Vec2, viewport: Rect) -> Vec2 {
+        (world - self.center()) * self.scale_in(viewport) + viewport.center()
+    }
+
+    fn screen_to_world_in(&self, screen: Vec2, viewport: Rect) -> Vec2 {
+        (screen - viewport.center()) / self.scale_in(viewport) + self.center()
+    }
+
+    /// Visible area in world coordinates.
+    pub fn view_rect(&self) -> Rect {
+        let size = self.viewport().size() / self.scale();
+        let top_left = self.center() - size / 2.;
+        Rect::new(top_left.x, top_left.y, size.x, size.y)
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREENS: [Vec2; 4] = [
+        vec2(1920., 1080.),
+        vec2(1280., 720.),
+        vec2(800., 1200.),
+        vec2(333., 777.),
+    ];
+
+    /// Cameras at every combination of position, zoom and letterboxing.
+    fn cameras() -> Vec<Camera> {
+        let mut cameras = Vec::new();
+        for pos in [Vec2::ZERO, vec2(1234.5, -67.8), vec2(-5000., 9000.)] {
+            for zoom in [MIN_ZOOM, 1., 1.7, MAX_ZOOM] {
+                for resolution in [None, Some(DEFAULT_RESOLUTION)] {
+                    cameras.push(Camera {
+                        pos,
+                        zoom,
+                        resolution,
+                        shake_offset: vec2(3., -4.),
+                        ..Camera::new()
+                    });
+                }
+            }
+        }
+        cameras
+    }
+
+    fn assert_close(a: Vec2, b: Vec2, eps: f32) {
+        assert!(a.distance(b) <= eps, "{a} != {b}");
+    }
+
+    #[test]
+    fn world_screen_round_trip() {
+        for camera in cameras() {
+            for screen in SCREENS {
+                let viewport = camera.viewport_in(screen);
+                for x in -10..=10 {
+                    for y in -10..=10 {
+                        let world = camera.pos + vec2(x as f32, y as f32) * 173.1;
+                        let back = camera.screen_to_world_in(
+                            camera.world_to_screen_in(world, viewport),
+                            viewport,
+                        );
+                        assert_close(back, world, 1e-2);
+
+                        let pixel = vec2(x as f32 + 10., y as f32 + 10.) * screen / 20.;
+                        let back = camera.world_to_screen_in(
+                            camera.screen_to_world_in(pixel, viewport),
+                            viewport,
+                        );
+                        assert_close(back, pixel, 1e-2);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn center_maps_to_viewport_center() {
+        for camera in cameras() {
+            for screen in SCREENS {
+                let viewport = camera.viewport_in(screen);
+                assert_close(
+                    camera.world_to_screen_in(camera.center(), viewport),
+                    viewport.center(),
+                    1e-3,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn letterbox_keeps_aspect_and_fits() {
+        let camera = Camera {
+            resolution: Some(DEFAULT_RESOLUTION),
+            ..Camera::new()
+        };
+        for screen in SCREENS {
+            let viewport = camera.viewport_in(screen);
+            let aspect = DEFAULT_RESOLUTION.x / DEFAULT_RESOLUTION.y;
+            assert!((viewport.w / viewport.h - aspect).abs() < 1e-3);
+            assert!(viewport.x >= 0. && viewport.right() <= screen.x + 1e-3);
+            assert!(viewport.y >= 0. && viewport.bottom() <= screen.y + 1e-3);
+            // touches the window on at least one axis.
+            assert!(viewport.x.abs() < 1e-3 || viewport.y.abs() < 1e-3);
+        }
+    }
+}
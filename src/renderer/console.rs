@@ -0,0 +1,37 @@
+use macroquad::prelude::*;
+
+use crate::model::console::Console;
+
+const FONT_SIZE: f32 = 24.;
+const LINE_HEIGHT: f32 = 22.;
+/// Fraction of the screen height the console drops down to.
+const HEIGHT: f32 = 0.4;
+
+/// The console, drawn over everything else while open. Newest output is at the bottom.
+pub fn render_console(console: &Console) {
+    if !console.open {
+        return;
+    }
+    let height = screen_height() * HEIGHT;
+    draw_rectangle(0., 0., screen_width(), height, Color::new(0., 0., 0., 0.85));
+    draw_line(0., height, screen_width(), height, 2., GRAY);
+
+    let input_y = height - 10.;
+    // blink the cursor twice a second.
+    let cursor = if get_time().fract() < 0.5 { "_" } else { "" };
+    draw_text(
+        &format!("> {}{cursor}", console.input),
+        10.,
+        input_y,
+        FONT_SIZE,
+        YELLOW,
+    );
+    let mut y = input_y - LINE_HEIGHT * 1.5;
+    for line in console.output().iter().rev() {
+        if y < LINE_HEIGHT {
+            break;
+        }
+        draw_text(line, 10., y, FONT_SIZE, WHITE);
+        y -= LINE_HEIGHT;
+    }
+}
@@ -0,0 +1,195 @@
+use macroquad::prelude::*;
+
+use super::{camera::Camera, world::enemy_query_rect};
+use crate::{
+    assets::Assets,
+    model::{
+        DAMAGE_DIST, World,
+        debug::{DebugOverlay, DebugToggles, FRAME_HISTORY},
+        player::Player,
+        weapons::WeaponKind,
+    },
+    util::{angle_dir, normalize_angle},
+};
+
+const FONT_SIZE: f32 = 24.;
+const LINE_HEIGHT: f32 = 22.;
+const PANEL_POS: Vec2 = vec2(10., 130.);
+const PANEL_WIDTH: f32 = 380.;
+const GRAPH_SIZE: Vec2 = vec2(FRAME_HISTORY as f32 * 2., 100.);
+/// Frame time at the top of the graph, in seconds.
+const GRAPH_MAX: f32 = 1. / 20.;
+/// World units between grid lines.
+const GRID_CELL: f32 = 250.;
+
+/// Watch values, grouped by section, and the frame-time graph.
+pub fn render_overlay(overlay: &DebugOverlay) {
+    if !overlay.visible {
+        return;
+    }
+    let lines: usize = overlay.sections().map(|(_, el)| el.len() + 1).sum();
+    draw_rectangle(
+        PANEL_POS.x - 5.,
+        PANEL_POS.y - LINE_HEIGHT,
+        PANEL_WIDTH,
+        lines as f32 * LINE_HEIGHT + 10.,
+        Color::new(0., 0., 0., 0.6),
+    );
+    let mut y = PANEL_POS.y;
+    for (section, watches) in overlay.sections() {
+        draw_text(section.name(), PANEL_POS.x, y, FONT_SIZE, YELLOW);
+        y += LINE_HEIGHT;
+        for (name, value) in watches {
+            draw_text(
+                &format!("{name}: {value}"),
+                PANEL_POS.x + 10.,
+                y,
+                FONT_SIZE,
+                WHITE,
+            );
+            y += LINE_HEIGHT;
+        }
+    }
+    render_frame_graph(overlay);
+}
+
+fn render_frame_graph(overlay: &DebugOverlay) {
+    let origin = vec2(
+        screen_width() - GRAPH_SIZE.x - 10.,
+        screen_height() - GRAPH_SIZE.y - 10.,
+    );
+    draw_rectangle(
+        origin.x,
+        origin.y,
+        GRAPH_SIZE.x,
+        GRAPH_SIZE.y,
+        Color::new(0., 0., 0., 0.6),
+    );
+    let bar_width = GRAPH_SIZE.x / FRAME_HISTORY as f32;
+    for (i, dt) in overlay.frame_times().iter().enumerate() {
+        let height = (dt / GRAPH_MAX).min(1.) * GRAPH_SIZE.y;
+        let color = if *dt > 1. / 30. {
+            RED
+        } else if *dt > 1. / 60. {
+            ORANGE
+        } else {
+            GREEN
+        };
+        draw_rectangle(
+            origin.x + i as f32 * bar_width,
+            origin.y + GRAPH_SIZE.y - height,
+            bar_width,
+            height,
+            color,
+        );
+    }
+    for (fps, label) in [(60., "60"), (30., "30")] {
+        let y = origin.y + GRAPH_SIZE.y * (1. - 1. / fps / GRAPH_MAX);
+        draw_line(origin.x, y, origin.x + GRAPH_SIZE.x, y, 1., GRAY);
+        draw_text(label, origin.x - 25., y + 5., 18., GRAY);
+    }
+}
+
+/// World-space visualisations, drawn on top of the world and below the UI.
+pub fn render_world_debug(
+    world: &World,
+    player: &Player,
+    assets: &Assets,
+    camera: &Camera,
+    toggles: &DebugToggles,
+) {
+    if toggles.grid {
+        draw_grid(assets, camera);
+    }
+    if toggles.hitboxes {
+        draw_hitboxes(world, assets, camera);
+    }
+    if toggles.weapon_cone {
+        draw_weapon_cone(world, player, camera);
+    }
+}
+
+fn draw_grid(assets: &Assets, camera: &Camera) {
+    let view = camera.view_rect();
+    let color = Color::new(1., 1., 1., 0.25);
+    let mut x = (view.left() / GRID_CELL).floor() * GRID_CELL;
+    while x <= view.right() {
+        let top = camera.world_to_screen(vec2(x, view.top()));
+        let bottom = camera.world_to_screen(vec2(x, view.bottom()));
+        draw_line(top.x, top.y, bottom.x, bottom.y, 1., color);
+        draw_text(&format!("{x}"), top.x + 2., top.y + 16., 16., color);
+        x += GRID_CELL;
+    }
+    let mut y = (view.top() / GRID_CELL).floor() * GRID_CELL;
+    while y <= view.bottom() {
+        let left = camera.world_to_screen(vec2(view.left(), y));
+        let right = camera.world_to_screen(vec2(view.right(), y));
+        draw_line(left.x, left.y, right.x, right.y, 1., color);
+        draw_text(&format!("{y}"), left.x + 2., left.y - 2., 16., color);
+        y += GRID_CELL;
+    }
+    // enemies outside this rectangle are skipped without being looked at.
+    let query = enemy_query_rect(&assets.enemies, camera);
+    let top_left = camera.world_to_screen(query.point());
+    let size = query.size() * camera.scale();
+    draw_rectangle_lines(top_left.x, top_left.y, size.x, size.y, 3., SKYBLUE);
+}
+
+fn draw_hitboxes(world: &World, assets: &Assets, camera: &Camera) {
+    let scale = camera.scale();
+    let player = camera.world_to_screen(world.player_pos);
+    draw_circle_lines(player.x, player.y, DAMAGE_DIST * scale, 2., GREEN);
+    for enemy in world
+        .horde
+        .query_rect(enemy_query_rect(&assets.enemies, camera))
+    {
+        let pos = camera.world_to_screen(enemy.loc);
+        let color = if enemy.health > 0. { MAGENTA } else { GRAY };
+        draw_circle(pos.x, pos.y, 3., color);
+        if let Some(source) = assets
+            .enemies
+            .get(enemy.id)
+            .and_then(|info| enemy.anim.frame(&info.animation))
+        {
+            let size = source.size() * scale;
+            draw_rectangle_lines(
+                pos.x - size.x / 2.,
+                pos.y - size.y / 2.,
+                size.x,
+                size.y,
+                1.,
+                color,
+            );
+        }
+    }
+}
+
+fn draw_weapon_cone(world: &World, player: &Player, camera: &Camera) {
+    let WeaponKind::Melee { range, angle } = player.weapon.kind else {
+        return;
+    };
+    let kind = player.weapon.kind;
+    let center = camera.world_to_screen(world.player_pos);
+    let scale = camera.scale();
+    let left = normalize_angle(player.aim + angle / 2.);
+    // a fan of triangles approximating the sector the weapon hits.
+    const SEGMENTS: usize = 16;
+    let fill = Color::new(1., 1., 0., 0.2);
+    for i in 0..SEGMENTS {
+        let a = left - angle * i as f32 / SEGMENTS as f32;
+        let b = left - angle * (i + 1) as f32 / SEGMENTS as f32;
+        draw_triangle(
+            center,
+            center + angle_dir(a) * range * scale,
+            center + angle_dir(b) * range * scale,
+            fill,
+        );
+    }
+    let view = camera.view_rect();
+    for enemy in world.horde.query_rect(view) {
+        if enemy.health > 0. && kind.collides(world.player_pos, enemy.loc, player.aim) {
+            let pos = camera.world_to_screen(enemy.loc);
+            draw_circle_lines(pos.x, pos.y, 12., 3., YELLOW);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use macroquad::prelude::*;
+
+/// Where a vertical list of menu items is drawn. Used for drawing and for mouse hit tests,
+/// so what gets clicked is what is on screen.
+#[derive(Debug, Clone, Copy)]
+pub struct MenuLayout {
+    /// Left end of the first item's baseline.
+    pub pos: Vec2,
+    /// Distance between baselines.
+    pub spacing: f32,
+    pub font_size: f32,
+}
+
+impl MenuLayout {
+    /// Screen rectangle covered by item `i`.
+    pub fn item_rect(&self, i: usize, label: &str) -> Rect {
+        let dims = measure_text(label, None, self.font_size as u16, 1.);
+        let baseline = self.pos.y + i as f32 * self.spacing;
+        Rect::new(
+            self.pos.x,
+            baseline - dims.offset_y,
+            dims.width,
+            dims.height.max(self.font_size),
+        )
+    }
+    /// Index of the item under `point`, if any.
+    pub fn hit<S: AsRef<str>>(&self, items: &[S], point: Vec2) -> Option<usize> {
+        items
+            .iter()
+            .enumerate()
+            .position(|(i, label)| self.item_rect(i, label.as_ref()).contains(point))
+    }
+    /// Draws `items`, highlighting the one at `selection`.
+    pub fn draw<S: AsRef<str>>(&self, items: &[S], selection: u32) {
+        for (i, label) in items.iter().enumerate() {
+            draw_text(
+                label.as_ref(),
+                self.pos.x,
+                self.pos.y + i as f32 * self.spacing,
+                self.font_size,
+                if selection == i as u32 { YELLOW } else { WHITE },
+            );
+        }
+    }
+}
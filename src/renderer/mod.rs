@@ -1,28 +1,75 @@
-use futures::channel::mpsc::UnboundedReceiver;
+use macroquad::prelude::*;
 
 use crate::{
-    errors::Nresult,
-    model::{GameModel, Status, player::Player},
+    assets::Assets,
+    input::{AimSource, InputMan},
+    model::{Status, World, console::Console, debug::DebugOverlay, player::Player},
+    settings::Settings,
+    util::angle_dir,
 };
 
+pub mod camera;
+pub mod console;
+pub mod debug;
+pub mod menu;
 pub mod ui;
 pub mod world;
 
-pub struct Renderer {}
+/// How far ahead of the player the crosshair sits while aiming with a stick, in world units.
+const STICK_CROSSHAIR_DIST: f32 = 150.;
+
+pub struct Renderer {
+    pub camera: camera::Camera,
+}
 impl Renderer {
-    pub async fn render_ui(&mut self, stat: &Status, player: &Player) {
-        ui::render_ui(stat, player).await
+    pub async fn render_ui(
+        &mut self,
+        stat: &Status,
+        world: &World,
+        player: &Player,
+        assets: &Assets,
+        settings: &Settings,
+        input: &InputMan,
+    ) {
+        let crosshair = match input.aim_source {
+            AimSource::Mouse => input.mouse.pos(),
+            AimSource::Pad => self
+                .camera
+                .world_to_screen(world.player_pos + angle_dir(player.aim) * STICK_CROSSHAIR_DIST),
+        };
+        ui::render_ui(stat, player, assets, settings, &input.actions, crosshair).await
     }
 
-    pub async fn render_world(&mut self, world: &crate::model::World, player: &Player) {
-        world::render_world(&world, &player).await
+    /// Developer tools, drawn over the UI.
+    pub fn render_debug(&mut self, overlay: &DebugOverlay, console: &Console) {
+        debug::render_overlay(overlay);
+        console::render_console(console);
     }
 
-    pub fn render_debug(&mut self, rx: &mut UnboundedReceiver<String>) -> Nresult {
-        ui::render_dbg(rx)
+    pub async fn render_world(
+        &mut self,
+        world: &World,
+        player: &Player,
+        assets: &Assets,
+        overlay: &DebugOverlay,
+        attacking: bool,
+    ) {
+        self.camera
+            .follow(world.player_pos, world.map_size(assets), get_frame_time());
+        world::render_world(
+            world,
+            player,
+            assets,
+            &self.camera,
+            &overlay.toggles,
+            attacking,
+        )
+        .await
     }
 
     pub fn new() -> Renderer {
-        Renderer {}
+        Renderer {
+            camera: camera::Camera::new(),
+        }
     }
 }
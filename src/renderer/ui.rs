@@ -1,56 +1,45 @@
-use std::sync::atomic::AtomicBool;
-
 use crate::{
-    assets::CROSSHAIR_TEX,
-    errors::Nresult,
-    model::{GameMode, GameModel, SAVE_PATHBUF_CACHE, Status, TitlePhase, player::Player},
+    assets::{Assets, Sprite},
+    input::actions::{Action, ActionMap},
+    model::{
+        ControlsScreen, DEFEATED, GAMEOVER_OPTIONS, GameMode, PAUSE_OPTIONS, RunStats, SaveEdit,
+        SettingsRow, SettingsScreen, Status, TitlePhase,
+        player::Player,
+        save::{SAVE_CACHE, SaveEntry},
+    },
+    renderer::menu::MenuLayout,
+    settings::{Settings, WindowMode},
+    util::format_unix_time,
 };
-use futures::channel::mpsc::UnboundedReceiver;
 use macroquad::prelude::*;
 
-pub static LOG: AtomicBool = AtomicBool::new(false);
-
-pub async fn render_ui(status: &Status, player: &Player) {
+pub async fn render_ui(
+    status: &Status,
+    player: &Player,
+    assets: &Assets,
+    settings: &Settings,
+    actions: &ActionMap,
+    crosshair: Vec2,
+) {
     match &status.mode {
-        GameMode::Title { phase } => render_title(phase),
-        GameMode::Pause => render_pause_menu(),
-        GameMode::Play => render_play(player),
-        GameMode::GameOver => render_gameover(),
-    }
-}
-
-const DEBUG_FONT_SIZE: f32 = 50.;
-
-pub fn render_dbg(rx: &mut UnboundedReceiver<String>) -> Nresult {
-    if !LOG.load(std::sync::atomic::Ordering::Relaxed) {
-        return Ok(());
-    }
-    let mut targets = Vec::new();
-    while let Ok(Some(s)) = rx.try_next() {
-        targets.push(s);
-    }
-    for i in 0..targets.len() {
-        draw_text(
-            &targets[i],
-            0.,
-            i as f32 * DEBUG_FONT_SIZE + 75. + DEBUG_FONT_SIZE * i as f32,
-            DEBUG_FONT_SIZE,
-            WHITE,
-        );
+        GameMode::Title { phase } => render_title(phase, settings, actions, &status.rename),
+        GameMode::Pause {
+            selection,
+            settings: screen,
+        } => match screen {
+            Some(screen) => render_settings(settings, actions, screen),
+            None => render_pause_menu(*selection),
+        },
+        GameMode::Play => render_play(player, &assets.crosshair, crosshair),
+        GameMode::GameOver { selection } => render_gameover(&status.run, *selection),
     }
-    Ok(())
 }
 
-static WAS_PAUSE: AtomicBool = AtomicBool::new(false);
-
-fn render_play(model: &Player) {
-    if WAS_PAUSE.load(std::sync::atomic::Ordering::Relaxed) {
-        miniquad::window::show_mouse(false);
-        WAS_PAUSE.store(false, std::sync::atomic::Ordering::Release);
-    }
+/// `crosshair_pos` is where the crosshair goes, in screen pixels.
+fn render_play(model: &Player, crosshair: &Sprite, crosshair_pos: Vec2) {
     render_health(model);
-    render_defeated(crate::model::DEFEATED.load(std::sync::atomic::Ordering::Relaxed));
-    render_crosshair();
+    render_defeated(DEFEATED.load(std::sync::atomic::Ordering::Relaxed));
+    render_crosshair(crosshair, crosshair_pos);
 }
 
 fn render_defeated(defeated: u32) {
@@ -63,7 +52,7 @@ fn render_defeated(defeated: u32) {
     );
 }
 
-fn render_title(phase: &TitlePhase) {
+fn render_title(phase: &TitlePhase, settings: &Settings, actions: &ActionMap, rename: &str) {
     match phase {
         TitlePhase::Start => {
             clear_background(BLACK);
@@ -75,27 +64,153 @@ fn render_title(phase: &TitlePhase) {
                 WHITE,
             );
             draw_text(
-                "Press Enter to Start",
+                &format!("Press {} to Start", actions.hint(Action::MenuConfirm)),
                 screen_width() / 2.0 - 140.0,
                 screen_height() / 2.0 + 40.0,
                 30.0,
                 GRAY,
             );
+            let movement = [
+                Action::MoveUp,
+                Action::MoveLeft,
+                Action::MoveDown,
+                Action::MoveRight,
+            ]
+            .map(|el| actions.hint(el))
+            .join("/");
             draw_text(
-                "WASD and click. / to open debug overlay.",
+                &format!(
+                    "{movement} to move, {} to attack, {} to dash, wheel to zoom. {} for debug overlay.",
+                    actions.hint(Action::Attack),
+                    actions.hint(Action::Dash),
+                    actions.hint(Action::ToggleDebug),
+                ),
                 screen_width() / 2. - 250.,
                 screen_height() / 2. + 200.,
                 30.,
                 GRAY,
             );
+            draw_text(
+                &format!(
+                    "{} for settings and controls. {} opens the console in game.",
+                    actions.hint(Action::OpenSettings),
+                    actions.hint(Action::ToggleConsole),
+                ),
+                screen_width() / 2. - 250.,
+                screen_height() / 2. + 240.,
+                30.,
+                GRAY,
+            );
         }
-        TitlePhase::Menu(selection) => {
-            render_menu(*selection);
-        }
-        _ => {
-            clear_background(BLACK);
+        TitlePhase::Menu {
+            selection,
+            scroll,
+            edit,
+        } => render_menu(*selection, *scroll, *edit, actions, rename),
+        TitlePhase::Settings(screen) => render_settings(settings, actions, screen),
+    }
+}
+
+fn render_settings(settings: &Settings, actions: &ActionMap, screen: &SettingsScreen) {
+    if let Some(controls) = &screen.controls {
+        render_controls(actions, controls);
+        return;
+    }
+    clear_background(BLACK);
+    draw_text("SETTINGS", 100., 100., 100., WHITE);
+    let layout = settings_menu_layout();
+    let items = settings_menu_items(settings);
+    layout.draw(&items, screen.selection);
+    draw_text(
+        &format!(
+            "{} and {} to change, {} to toggle, {} to go back.",
+            actions.hint(Action::MenuLeft),
+            actions.hint(Action::MenuRight),
+            actions.hint(Action::MenuConfirm),
+            actions.hint(Action::MenuBack),
+        ),
+        layout.pos.x,
+        layout.pos.y + items.len() as f32 * layout.spacing + 20.,
+        30.,
+        GRAY,
+    );
+}
+
+/// Labels of the settings screen, every `SettingsRow` with its current value.
+pub fn settings_menu_items(settings: &Settings) -> Vec<String> {
+    let on_off = |value: bool| if value { "on" } else { "off" };
+    let percent = |volume: f32| format!("{:.0}%", volume * 100.);
+    let value = |row: SettingsRow| match row {
+        SettingsRow::Window => Some(match settings.window_mode {
+            WindowMode::Windowed => "windowed".to_owned(),
+            WindowMode::Fullscreen => "fullscreen".to_owned(),
+        }),
+        SettingsRow::MasterVolume => Some(percent(settings.master_volume)),
+        SettingsRow::MusicVolume => Some(percent(settings.music_volume)),
+        SettingsRow::SfxVolume => Some(percent(settings.sfx_volume)),
+        SettingsRow::DebugOverlay => Some(on_off(settings.debug_overlay).to_owned()),
+        SettingsRow::Zoom => Some(format!("{:.2}x", settings.zoom)),
+        SettingsRow::Letterbox => Some(on_off(settings.letterbox).to_owned()),
+        SettingsRow::Controls | SettingsRow::Back => None,
+    };
+    SettingsRow::ALL
+        .into_iter()
+        .map(|row| match value(row) {
+            Some(value) => format!("{}: {value}", row.name()),
+            None => row.name().to_owned(),
+        })
+        .collect()
+}
+
+pub fn settings_menu_layout() -> MenuLayout {
+    MenuLayout {
+        pos: vec2(100., 200.),
+        spacing: 40.,
+        font_size: 30.,
+    }
+}
+
+fn render_controls(actions: &ActionMap, screen: &ControlsScreen) {
+    let ControlsScreen {
+        selection,
+        waiting,
+        conflict,
+    } = *screen;
+    clear_background(BLACK);
+    draw_text("CONTROLS", 100., 100., 100., WHITE);
+    let color = |i: usize| {
+        if selection as usize != i {
+            WHITE
+        } else if waiting {
+            ORANGE
+        } else {
+            YELLOW
         }
+    };
+    for (i, action) in Action::ALL.into_iter().enumerate() {
+        let y = 160. + i as f32 * 35.;
+        let bindings: Vec<_> = actions
+            .get(action)
+            .iter()
+            .map(|el| el.to_string())
+            .collect();
+        draw_text(action.name(), 100., y, 30., color(i));
+        draw_text(&bindings.join(", "), 500., y, 30., color(i));
     }
+    let y = 160. + Action::ALL.len() as f32 * 35.;
+    draw_text("Reset to defaults", 100., y, 30., color(Action::ALL.len()));
+    let hint = if waiting {
+        "Press the new key or mouse button.".to_owned()
+    } else if let Some(other) = conflict {
+        format!("Already used by \"{}\".", other.name())
+    } else {
+        format!(
+            "{} to rebind, {} to go back.",
+            actions.hint(Action::MenuConfirm),
+            actions.hint(Action::MenuBack)
+        )
+    };
+    draw_text(&hint, 100., y + 60., 30., GRAY);
 }
 
 fn render_health(player: &Player) {
@@ -110,48 +225,120 @@ fn render_health(player: &Player) {
     );
 }
 
-fn render_gameover() {
+fn render_gameover(run: &RunStats, selection: u32) {
     clear_background(BLACK);
     draw_text("GAME OVER", 100., screen_height() / 2.0 - 100., 100., WHITE);
+    let time = run.time as u32;
+    let lines = [
+        format!("Time survived: {}:{:02}", time / 60, time % 60),
+        format!(
+            "Defeated: {}",
+            DEFEATED.load(std::sync::atomic::Ordering::Relaxed)
+        ),
+        format!("Damage dealt: {:.0}", run.damage_dealt),
+        format!("Level: {}", run.level),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(
+            line,
+            100.,
+            screen_height() / 2.0 + i as f32 * 40.,
+            30.,
+            GRAY,
+        );
+    }
+    gameover_menu_layout().draw(&GAMEOVER_OPTIONS, selection);
 }
 
-fn render_menu(selection: u32) {
+pub fn gameover_menu_layout() -> MenuLayout {
+    MenuLayout {
+        pos: vec2(100., screen_height() / 2.0 + 200.),
+        spacing: 40.,
+        font_size: 30.,
+    }
+}
+
+fn render_menu(selection: u32, scroll: u32, edit: SaveEdit, actions: &ActionMap, rename: &str) {
     clear_background(BLACK);
-    draw_text(
-        "SAVE FILES",
-        100.0,
-        screen_height() / 2.0 - 100.0,
-        100.0,
-        WHITE,
+    draw_text("SAVE FILES", 100.0, 120.0, 100.0, WHITE);
+    let mut items = save_menu_items(&SAVE_CACHE.read().unwrap());
+    if edit == SaveEdit::Rename
+        && let Some(item) = items.get_mut(selection as usize)
+    {
+        *item = format!("{rename}_");
+    }
+    let layout = save_menu_layout();
+    let start = (scroll as usize).min(items.len());
+    let end = (start + SAVE_MENU_ROWS as usize).min(items.len());
+    let shown = &items[start..end];
+    layout.draw(shown, selection.wrapping_sub(scroll));
+    // rows scrolled out of view are marked on the side they are hidden at.
+    let below = layout.pos.y + shown.len() as f32 * layout.spacing;
+    if start > 0 {
+        let above = layout.pos.y - layout.spacing;
+        draw_text("...", layout.pos.x, above, layout.font_size, GRAY);
+    }
+    if end < items.len() {
+        draw_text("...", layout.pos.x, below, layout.font_size, GRAY);
+    }
+    let (confirm, back) = (
+        actions.hint(Action::MenuConfirm),
+        actions.hint(Action::MenuBack),
     );
+    let hint = match edit {
+        SaveEdit::None => format!(
+            "{confirm} to play, {} to delete, {} to rename, {back} to go back.",
+            actions.hint(Action::MenuDelete),
+            actions.hint(Action::MenuRename),
+        ),
+        SaveEdit::Delete => format!("Delete this save? {confirm} to confirm, {back} to cancel."),
+        SaveEdit::Rename => format!("Type a new name. {confirm} to confirm, {back} to cancel."),
+    };
     draw_text(
-        "NEW GAME",
-        100.0,
-        screen_height() / 2.0 + 20.0,
-        30.0,
-        if selection == 0 { YELLOW } else { WHITE },
+        &hint,
+        layout.pos.x,
+        below + layout.spacing + 20.,
+        30.,
+        if edit == SaveEdit::Delete { RED } else { GRAY },
     );
-    let lock = SAVE_PATHBUF_CACHE.read().unwrap();
-    for i in 0..lock.len() {
-        draw_text(
-            lock[i].file_name().unwrap().to_str().unwrap(),
-            screen_width() / 2.0 - 100.0,
-            screen_height() / 2.0 + i as f32 * 20.0,
-            30.0,
-            if selection == i as u32 + 1 {
-                YELLOW
-            } else {
-                WHITE
-            },
-        );
-    }
 }
 
-fn render_pause_menu() {
-    if !WAS_PAUSE.load(std::sync::atomic::Ordering::Relaxed) {
-        WAS_PAUSE.store(true, std::sync::atomic::Ordering::Release);
-        miniquad::window::show_mouse(true);
+/// Labels of the save browser: "new game", then every save with its metadata.
+pub fn save_menu_items(saves: &[SaveEntry]) -> Vec<String> {
+    let describe = |save: &SaveEntry| {
+        let date = save.modified.map_or_else(|| "?".into(), format_unix_time);
+        match &save.data {
+            Some(data) => {
+                let time = data.time as u32;
+                format!(
+                    "{}   {date}   map {}   level {}   {}:{:02} played",
+                    save.name,
+                    data.map,
+                    data.level.max(data.map + 1),
+                    time / 60,
+                    time % 60
+                )
+            }
+            None => format!("{}   {date}   (unreadable)", save.name),
+        }
+    };
+    std::iter::once("NEW GAME".to_owned())
+        .chain(saves.iter().map(describe))
+        .collect()
+}
+
+/// Save browser rows shown at once, "new game" included. The rest scroll.
+pub const SAVE_MENU_ROWS: u32 = 10;
+
+pub fn save_menu_layout() -> MenuLayout {
+    MenuLayout {
+        pos: vec2(100., 220.),
+        spacing: 40.,
+        font_size: 30.,
     }
+}
+
+fn render_pause_menu(selection: u32) {
     draw_rectangle(
         50.0,
         50.0,
@@ -162,17 +349,29 @@ fn render_pause_menu() {
     draw_text(
         "Paused",
         screen_width() / 2.0 - 60.0,
-        screen_height() / 2.0,
+        screen_height() / 2.0 - 100.0,
         50.0,
         YELLOW,
     );
+    pause_menu_layout().draw(&PAUSE_OPTIONS, selection);
 }
-fn render_crosshair() {
-    let mouse_pos = mouse_position();
+
+pub fn pause_menu_layout() -> MenuLayout {
+    MenuLayout {
+        pos: vec2(screen_width() / 2.0 - 120.0, screen_height() / 2.0),
+        spacing: 50.,
+        font_size: 40.,
+    }
+}
+
+fn render_crosshair(crosshair: &Sprite, pos: Vec2) {
+    let Some(tex) = &crosshair.tex else {
+        return;
+    };
     draw_texture_ex(
-        &CROSSHAIR_TEX,
-        mouse_pos.0 - 25.,
-        mouse_pos.1 - 25.,
+        tex,
+        pos.x - 25.,
+        pos.y - 25.,
         WHITE,
         DrawTextureParams {
             dest_size: Some(Vec2 { x: 50., y: 50. }),
@@ -1,35 +1,65 @@
-use std::sync::atomic::AtomicBool;
-
+use super::{camera::Camera, debug::render_world_debug};
 use crate::{
-    assets::{self, get_map},
-    errors::{Nresult, Result},
-    model::{World, enemies::enemymap, player::Player, weapons::Weapon},
-    util::{PlayerAnimation, get_mouse_angle, get_mouse_angle_centered},
+    assets::{Assets, Sprite},
+    errors::Nresult,
+    model::{
+        World, debug::DebugToggles, enemies::enemymap::EnemyMap, player::Player, weapons::Weapon,
+    },
+    profiler,
+    util::{angle_dir, normalize_angle},
 };
-use macroquad::{miniquad::window::screen_size, prelude::*, telemetry::frame};
-
-pub async fn render_world(world: &World, player: &Player) {
-    let tex = get_map(world.map).unwrap();
-    let scale = get_world_scale() / 10.;
-    let screen = vec2(screen_width(), screen_height());
-    let half_screen = screen / 2.0;
-    let map_size = vec2(tex.width() as f32, tex.height() as f32);
-
-    let min_cam = half_screen / scale;
-    let max_cam = map_size - min_cam;
-    let camera_pos = world.player_pos.clamp(min_cam, max_cam);
-
-    draw_map(tex, camera_pos, scale)
-        .await
-        .unwrap_or_else(|err| error!("{}", err));
-    draw_weapon(&player.weapon, world.player_pos, camera_pos, scale, world);
-    draw_player(world, camera_pos, scale);
-    draw_enemies(world, camera_pos, scale);
+use macroquad::prelude::*;
+
+pub async fn render_world(
+    world: &World,
+    player: &Player,
+    assets: &Assets,
+    camera: &Camera,
+    toggles: &DebugToggles,
+    attacking: bool,
+) {
+    clear_background(GRAY);
+    match assets.map(world.map) {
+        Ok(map) => draw_map(map, camera)
+            .await
+            .unwrap_or_else(|err| error!("{}", err)),
+        Err(err) => error!("{}", err),
+    }
+    let _ = draw_weapon(
+        &player.weapon,
+        world.player_pos,
+        player.aim,
+        attacking,
+        camera,
+    );
+    draw_player(world, player, assets, camera);
+    profiler::time("draw_enemies", || {
+        draw_enemies(world, &assets.enemies, camera)
+    });
+    render_world_debug(world, player, assets, camera, toggles);
+    draw_letterbox(camera);
 }
 
-async fn draw_map(tex: &Texture2D, camera_pos: Vec2, scale: f32) -> Nresult {
-    let screen = vec2(screen_width(), screen_height());
-    let draw_pos = -camera_pos * scale + screen / 2.0;
+/// Blacks out whatever is outside the camera viewport.
+fn draw_letterbox(camera: &Camera) {
+    let viewport = camera.viewport();
+    let (w, h) = (screen_width(), screen_height());
+    if viewport.x > 0. {
+        draw_rectangle(0., 0., viewport.x, h, BLACK);
+        draw_rectangle(viewport.right(), 0., w - viewport.right(), h, BLACK);
+    }
+    if viewport.y > 0. {
+        draw_rectangle(0., 0., w, viewport.y, BLACK);
+        draw_rectangle(0., viewport.bottom(), w, h - viewport.bottom(), BLACK);
+    }
+}
+
+async fn draw_map(map: &Sprite, camera: &Camera) -> Nresult {
+    let Some(tex) = &map.tex else {
+        return Ok(());
+    };
+    let scale = camera.scale();
+    let draw_pos = camera.world_to_screen(Vec2::ZERO);
 
     draw_texture_ex(
         tex,
@@ -37,81 +67,71 @@ async fn draw_map(tex: &Texture2D, camera_pos: Vec2, scale: f32) -> Nresult {
         draw_pos.y,
         WHITE,
         DrawTextureParams {
-            dest_size: Some(vec2(scale * tex.width(), scale * tex.height())),
+            dest_size: Some(map.size * scale),
             ..Default::default()
         },
     );
     Ok(())
 }
 
-pub fn get_map_size(map: u32) -> Result<Vec2> {
-    let tex = get_map(map)?;
-    let max_size = get_world_scale();
-    Ok(vec2(max_size * tex.width(), max_size * tex.height()))
-}
+fn draw_player(world: &World, player: &Player, assets: &Assets, camera: &Camera) {
+    let Some(Sprite {
+        size,
+        tex: Some(tex),
+    }) = player.anim.frame(&assets.player)
+    else {
+        return;
+    };
 
-fn draw_player(world: &World, camera_pos: Vec2, scale: f32) {
-    let sprite = &assets::SPRITES[PlayerAnimation::Idle as usize];
-    let screen = vec2(screen_width(), screen_height());
-    let half_screen = screen / 2.0;
-
-    let player_screen_pos = (world.player_pos - camera_pos) * scale + half_screen;
-    let size = sprite.size() * scale;
+    let player_screen_pos = camera.world_to_screen(world.player_pos);
+    let size = *size * camera.scale();
 
     draw_texture_ex(
-        sprite,
+        tex,
         player_screen_pos.x - size.x / 2.,
         player_screen_pos.y - size.y / 2.,
         WHITE,
         DrawTextureParams {
             dest_size: Some(size),
-            flip_x: is_key_down(KeyCode::A),
+            flip_x: player.facing_left(),
             ..Default::default()
         },
     );
 }
 
+/// `attacking` is whether the attack action is held, which tints the cooldown line.
 fn draw_weapon(
     weapon: &Weapon,
     player_pos: Vec2,
-    camera_pos: Vec2,
-    scale: f32,
-    world: &World,
+    mangle: f32,
+    attacking: bool,
+    camera: &Camera,
 ) -> Nresult {
-    let center = (player_pos - camera_pos) * scale + vec2(screen_width(), screen_height()) / 2.0;
-    let wscale = scale;
+    let center = camera.world_to_screen(player_pos);
+    let wscale = camera.scale();
 
     match weapon.kind {
         crate::model::weapons::WeaponKind::Melee { range, angle } => {
-            let mangle = get_mouse_angle_centered(world);
+            let left = normalize_angle(mangle + angle / 2.0);
+            let right = normalize_angle(mangle - angle / 2.0);
 
-            draw_line(
-                center.x,
-                center.y,
-                center.x + range * (mangle + angle / 2.0).cos() * wscale,
-                center.y - range * (mangle + angle / 2.0).sin() * wscale,
-                5.0,
-                RED,
-            );
-            draw_line(
-                center.x,
-                center.y,
-                center.x + (range * wscale + 5.0) * (mangle - angle / 2.0).cos(),
-                center.y - (range * wscale + 5.0) * (mangle - angle / 2.0).sin(),
-                5.0,
-                RED,
-            );
+            // the same directions `WeaponKind::collides` measures against.
+            for edge in [left, right] {
+                let end = center + angle_dir(edge) * range * wscale;
+                draw_line(center.x, center.y, end.x, end.y, 5.0, RED);
+            }
 
             if weapon.cooldown_counter > 0.0 {
+                let end = center + angle_dir(mangle) * range * wscale;
                 draw_line(
                     center.x,
                     center.y,
-                    center.x + range * mangle.cos() * wscale,
-                    center.y - range * mangle.sin() * wscale,
+                    end.x,
+                    end.y,
                     5.0,
                     if weapon.cooldown_counter == weapon.cooldown {
                         GREEN
-                    } else if is_mouse_button_down(MouseButton::Left) {
+                    } else if attacking {
                         BLUE
                     } else {
                         RED
@@ -124,7 +144,7 @@ fn draw_weapon(
                 center.y,
                 50,
                 range * wscale,
-                -(mangle + angle / 2.0).to_degrees(),
+                -left.to_degrees(),
                 5.0,
                 angle.to_degrees(),
                 RED,
@@ -138,73 +158,51 @@ fn draw_weapon(
     Ok(())
 }
 
-const SCALE_DIV: f32 = 200.;
-
-pub fn get_world_scale() -> f32 {
-    Vec2::from(screen_size()).max_element() / SCALE_DIV
-}
-pub fn get_min_world_scale() -> f32 {
-    Vec2::from(screen_size()).min_element() / SCALE_DIV
+/// World rectangle searched for enemies that might be on screen.
+pub(super) fn enemy_query_rect(kinds: &EnemyMap, camera: &Camera) -> Rect {
+    let view = camera.view_rect();
+    // enemies are queried by their center, so grow the view by half a sprite on every side.
+    let margin = kinds.max_size() / 2.;
+    Rect::new(
+        view.x - margin.x,
+        view.y - margin.y,
+        view.w + margin.x * 2.,
+        view.h + margin.y * 2.,
+    )
 }
 
-fn draw_enemies(world: &World, camera_pos: Vec2, scale: f32) {
-    let screen = vec2(screen_width(), screen_height());
-    let half_screen = screen / 2.0;
-
-    for i in &world.horde {
-        let anim = &enemymap::get_enemy_info(i.id).unwrap().animation;
-        let tex = &anim.get((i.animation * 10.) as usize).unwrap().1;
-        let enemy_screen_pos = (i.loc - camera_pos) * scale + half_screen;
-        let tex_size = tex.size() * scale;
-        let draw_pos = enemy_screen_pos - tex_size / 2.0;
-
-        if draw_pos.cmple(screen - scale).all() && draw_pos.cmpge(Vec2::ONE * scale).all() {
-            draw_texture_ex(
-                tex,
-                draw_pos.x,
-                draw_pos.y,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(tex_size),
-                    ..Default::default()
-                },
-            );
+fn draw_enemies(world: &World, kinds: &EnemyMap, camera: &Camera) {
+    // every frame lives in the same atlas, so these draws batch into a handful of calls.
+    let Some(atlas) = kinds.atlas() else {
+        return;
+    };
+    let scale = camera.scale();
+    let view = camera.view_rect();
+
+    for i in world.horde.query_rect(enemy_query_rect(kinds, camera)) {
+        let Some(info) = kinds.get(i.id) else {
+            continue;
+        };
+        let anim = &info.animation;
+        let Some(&source) = i.anim.frame(anim) else {
+            continue;
+        };
+        let size = source.size();
+        let bounds = Rect::new(i.loc.x - size.x / 2., i.loc.y - size.y / 2., size.x, size.y);
+        if !bounds.overlaps(&view) {
+            continue;
         }
+        let draw_pos = camera.world_to_screen(bounds.point());
+        draw_texture_ex(
+            atlas,
+            draw_pos.x,
+            draw_pos.y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(size * scale),
+                source: Some(source),
+                ..Default::default()
+            },
+        );
     }
 }
-
-pub fn get_camera_pos(player_pos: Vec2, map_id: u32) -> Vec2 {
-    let tex = get_map(map_id).expect("Invalid map id");
-    let scale = get_world_scale() / 10.0;
-    let screen = vec2(screen_width(), screen_height());
-    let half_screen = screen / 2.0;
-
-    let map_size = vec2(tex.width() as f32, tex.height() as f32);
-    let min_cam = half_screen / scale;
-    let max_cam = map_size - min_cam;
-
-    player_pos.clamp(min_cam, max_cam)
-}
-
-pub fn get_player_screen_pos(player_pos: Vec2, map_id: u32) -> Vec2 {
-    let cam_pos = get_camera_pos(player_pos, map_id);
-    let scale = get_world_scale() / 10.0;
-    let screen = vec2(screen_width(), screen_height());
-    let half_screen = screen / 2.0;
-
-    (player_pos - cam_pos) * scale + half_screen
-}
-
-pub fn player_on_scr(world: &World) -> Vec2 {
-    let tex = get_map(world.map).unwrap();
-    let scale = get_world_scale() / 10.;
-    let screen = vec2(screen_width(), screen_height());
-    let half_screen = screen / 2.0;
-    let map_size = vec2(tex.width() as f32, tex.height() as f32);
-
-    let min_cam = half_screen / scale;
-    let max_cam = map_size - min_cam;
-    let camera_pos = world.player_pos.clamp(min_cam, max_cam);
-
-    (world.player_pos - camera_pos) * scale + vec2(screen_width(), screen_height()) / 2.0
-}
@@ -0,0 +1,231 @@
+use std::{fs::File, path::Path};
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{GameError, Nresult, Result},
+    renderer::camera::{Camera, DEFAULT_RESOLUTION, MAX_ZOOM, MIN_ZOOM},
+};
+
+/// Lives next to `model::SAVE_LOC`. Key bindings are kept apart in `actions::BINDINGS_LOC`.
+pub const SETTINGS_LOC: &str = "settings.json";
+/// Volume change per step in the settings screen.
+pub const VOLUME_STEP: f32 = 0.1;
+/// Zoom change per step in the settings screen.
+pub const ZOOM_STEP: f32 = 0.25;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WindowMode {
+    Windowed,
+    Fullscreen,
+}
+
+/// Everything the player can configure besides key bindings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window_mode: WindowMode,
+    /// Window size in windowed mode. Only read at startup.
+    pub window_size: [u32; 2],
+    /// Volumes in `0..=1`. Music and effects are scaled by `master_volume`.
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    /// Whether the debug overlay starts out shown.
+    pub debug_overlay: bool,
+    /// Camera zoom at the start of a session, in `MIN_ZOOM..=MAX_ZOOM`.
+    pub zoom: f32,
+    pub letterbox: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            window_mode: WindowMode::Windowed,
+            window_size: [1280, 720],
+            master_volume: 0.8,
+            music_volume: 0.7,
+            sfx_volume: 1.,
+            debug_overlay: false,
+            zoom: 1.,
+            letterbox: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads and validates `path`. Fields the file doesn't mention keep their defaults.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|_| GameError::NotFoundError(path.to_path_buf()))?;
+        let settings: Settings = serde_json::from_reader(file)
+            .map_err(|e| GameError::InvalidConfig(format!("{}: {e}", path.display())))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Loads `SETTINGS_LOC`. A missing file silently gives the defaults, a broken one
+    /// gives the defaults and the reason.
+    pub fn load_or_default() -> (Self, Option<GameError>) {
+        match Self::load(Path::new(SETTINGS_LOC)) {
+            Ok(settings) => (settings, None),
+            Err(GameError::NotFoundError(_)) => (Self::default(), None),
+            Err(e) => (Self::default(), Some(e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Nresult {
+        self.validate()?;
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self)
+            .map_err(|e| GameError::Unexpected(format!("{}: {e}", path.display())))
+    }
+
+    pub fn validate(&self) -> Nresult {
+        for (name, volume) in [
+            ("master_volume", self.master_volume),
+            ("music_volume", self.music_volume),
+            ("sfx_volume", self.sfx_volume),
+        ] {
+            if !(0. ..=1.).contains(&volume) {
+                return Err(GameError::InvalidConfig(format!(
+                    "{name} must be between 0 and 1, got {volume}"
+                )));
+            }
+        }
+        if !(MIN_ZOOM..=MAX_ZOOM).contains(&self.zoom) {
+            return Err(GameError::InvalidConfig(format!(
+                "zoom must be between {MIN_ZOOM} and {MAX_ZOOM}, got {}",
+                self.zoom
+            )));
+        }
+        if self.window_size.contains(&0) {
+            return Err(GameError::InvalidConfig(format!(
+                "window_size must not be zero, got {:?}",
+                self.window_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// Window configuration for `macroquad::main`.
+    pub fn window_conf(&self) -> Conf {
+        Conf {
+            window_title: "PIXEL_REBELS".to_owned(),
+            window_width: self.window_size[0] as i32,
+            window_height: self.window_size[1] as i32,
+            fullscreen: self.window_mode == WindowMode::Fullscreen,
+            window_resizable: true,
+            ..Default::default()
+        }
+    }
+
+    /// Pushes the settings that can change while running into the game.
+    /// `debug_overlay` is only a default, so it is applied once by `GameModel::new`.
+    /// The window mode belongs to the window, so the main loop applies that one.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.set_zoom(self.zoom);
+        camera.resolution = self.letterbox.then_some(DEFAULT_RESOLUTION);
+    }
+
+    /// Moves a volume by `steps` multiples of `VOLUME_STEP`, staying in `0..=1`.
+    pub fn step_volume(volume: &mut f32, steps: f32) {
+        let max = (1. / VOLUME_STEP).round();
+        *volume = ((*volume / VOLUME_STEP).round() + steps).clamp(0., max) / max;
+    }
+
+    /// Moves a volume one `VOLUME_STEP` up, going from full back to silent.
+    pub fn cycle_volume(volume: &mut f32) {
+        if *volume >= 1. {
+            *volume = 0.;
+        } else {
+            Self::step_volume(volume, 1.);
+        }
+    }
+
+    pub fn step_zoom(&mut self, steps: f32) {
+        self.zoom = (self.zoom + steps * ZOOM_STEP).clamp(MIN_ZOOM, MAX_ZOOM);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume_after(volume: f32, steps: f32) -> f32 {
+        let mut volume = volume;
+        Settings::step_volume(&mut volume, steps);
+        volume
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Settings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_out_of_range_values() {
+        let broken = [
+            Settings {
+                master_volume: 1.1,
+                ..Default::default()
+            },
+            Settings {
+                music_volume: -0.1,
+                ..Default::default()
+            },
+            Settings {
+                sfx_volume: f32::NAN,
+                ..Default::default()
+            },
+            Settings {
+                zoom: MIN_ZOOM - 0.1,
+                ..Default::default()
+            },
+            Settings {
+                zoom: MAX_ZOOM + 0.1,
+                ..Default::default()
+            },
+            Settings {
+                window_size: [0, 720],
+                ..Default::default()
+            },
+        ];
+        for settings in broken {
+            assert!(
+                matches!(settings.validate(), Err(GameError::InvalidConfig(_))),
+                "{settings:?}"
+            );
+        }
+        let edges = Settings {
+            master_volume: 0.,
+            music_volume: 1.,
+            zoom: MAX_ZOOM,
+            ..Default::default()
+        };
+        assert!(edges.validate().is_ok());
+    }
+
+    #[test]
+    fn step_volume_moves_on_the_grid_and_clamps() {
+        assert!((volume_after(0.5, 1.) - 0.6).abs() < 1e-6);
+        assert!((volume_after(0.5, -2.) - 0.3).abs() < 1e-6);
+        // off-grid volumes from a hand-edited file snap to the nearest step first.
+        assert!((volume_after(0.33, 1.) - 0.4).abs() < 1e-6);
+        assert_eq!(volume_after(1., 1.), 1.);
+        assert_eq!(volume_after(0.95, 3.), 1.);
+        assert_eq!(volume_after(0., -1.), 0.);
+        assert_eq!(volume_after(0.2, -5.), 0.);
+    }
+
+    #[test]
+    fn cycle_volume_wraps_past_full() {
+        let mut volume = 0.9;
+        Settings::cycle_volume(&mut volume);
+        assert_eq!(volume, 1.);
+        Settings::cycle_volume(&mut volume);
+        assert_eq!(volume, 0.);
+        Settings::cycle_volume(&mut volume);
+        assert!((volume - VOLUME_STEP).abs() < 1e-6);
+    }
+}
@@ -1,23 +1,21 @@
 use std::f32::consts::PI;
-use std::sync::Arc;
-use std::{process::exit, sync::atomic::AtomicBool};
+use std::sync::atomic::{AtomicBool, AtomicI32};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_std::sync::RwLock;
-use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
-use macroquad::miniquad::window::screen_size;
 use macroquad::prelude::*;
-use num_enum::{IntoPrimitive, TryFromPrimitive};
-use once_cell::sync::OnceCell;
 use rayon::iter::{
     IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator,
 };
+use signal_hook::consts::{SIGINT, SIGTERM};
 
 use crate::errors::{GameError, Nresult, Result};
-use crate::model::World;
 use crate::model::enemies::Enemy;
-use crate::renderer::world::player_on_scr;
 
+/// Set by the panic hook. The main loop quits once it sees it.
 pub static INTERRUPT: AtomicBool = AtomicBool::new(false);
+/// Signal number of a pending shutdown request, 0 if there is none.
+static SHUTDOWN_SIGNAL: AtomicI32 = AtomicI32::new(0);
 
 pub static DELTA_TIME: RwLock<f32> = RwLock::new(0.0);
 
@@ -27,109 +25,98 @@ pub async fn update_delta_time() -> Nresult {
     Ok(())
 }
 
-pub static DEBUG_TX: OnceCell<Arc<UnboundedSender<String>>> = OnceCell::new();
+/// Where the model reads time from. Backends implement this, so the model can run without a window.
+pub trait Clock {
+    /// Seconds the last frame took.
+    fn frame_time(&self) -> f32;
+    /// Seconds since the game started.
+    fn time(&self) -> f64;
+    fn fps(&self) -> i32;
+}
+
+/// Macroquad's frame timer. Needs a window.
+pub struct MacroquadClock;
+
+impl Clock for MacroquadClock {
+    fn frame_time(&self) -> f32 {
+        get_frame_time()
+    }
+    fn time(&self) -> f64 {
+        get_time()
+    }
+    fn fps(&self) -> i32 {
+        get_fps()
+    }
+}
+
+/// Every frame takes `dt` seconds and time stands still, for tests.
+#[cfg(test)]
+pub struct FixedClock {
+    pub dt: f32,
+}
 
-pub async fn create_mpsc() -> crate::errors::Result<UnboundedReceiver<String>> {
-    let (tx, rx) = mpsc::unbounded();
-    DEBUG_TX
-        .set(Arc::new(tx))
-        .map_err(|_| GameError::Misc("Tried to create debug mpsc twice.".into()))?;
-    Ok(rx)
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn frame_time(&self) -> f32 {
+        self.dt
+    }
+    fn time(&self) -> f64 {
+        0.
+    }
+    fn fps(&self) -> i32 {
+        (1. / self.dt).round() as i32
+    }
 }
 
 pub fn set_hooks() {
-    std::panic::set_hook(Box::new(|data| {
-        INTERRUPT.store(true, std::sync::atomic::Ordering::Release);
-        let payload = data.payload();
-        let msg = if let Some(s) = payload.downcast_ref::<GameError>() {
-            format!("{}", s)
-        } else if let Some(s) = payload.downcast_ref::<String>() {
-            format!("{}", s)
-        } else if let Some(s) = payload.downcast_ref::<&'static str>() {
-            format!("{}", s)
-        } else {
-            "Unknown Error.".to_owned()
-        };
-        let loc = data.location();
-        native_dialog::DialogBuilder::message()
-            .set_text(
-            format!("FATAL ERROR:\n{msg}\nLocation:\n{loc:#?}\n\nPlease report this at https://github.com/shuntia/pixel_rebels"))
-            .set_level(native_dialog::MessageLevel::Error)
-            .set_title(":(")
-            .alert()
-            .show()
-            .unwrap_or_else(|_|panic!("The dialog failed. I don't know why.\n\nMessage:\n{msg}\n\nLocation:\n{loc:#?}"));
-        eprintln!("panicked:\n{:#?}\nbacktrace:\n{:#?}", msg, data.location());
-    }));
-    ctrlc::set_handler(|| {
-        INTERRUPT.store(true, std::sync::atomic::Ordering::Release);
-        native_dialog::DialogBuilder::message()
-            .set_text("FATAL: control-c\n\nProcess killed by user")
-            .set_title("X(")
-            .set_level(native_dialog::MessageLevel::Error)
-            .alert()
-            .show()
-            .unwrap_or_else(|_| {
-                panic!("The dialog failed.\nFATAL: control-c\n\nProcess killed by user")
-            });
-        exit(130);
-    })
-    .unwrap_or_else(|_| error!("Failed to set ctrl-c hook."));
-    unsafe {
-        if signal_hook::low_level::register(15, || {
-            INTERRUPT.store(true, std::sync::atomic::Ordering::Release);
-            native_dialog::DialogBuilder::message()
-                .set_text("FATAL: SIGTERM\n\nProcess killed by user")
-                .set_title("X(")
-                .set_level(native_dialog::MessageLevel::Error)
-                .alert()
-                .show()
-                .unwrap_or_else(|_| {
-                    panic!("The dialog failed.\nFATAL: SIGTERM\n\nProcess killed by user")
-                });
-            exit(133);
-        })
-        .is_err()
-        {
-            error!("Failed to set SIGTERM hook.");
-        };
-        if signal_hook::low_level::register(6, || {
-            INTERRUPT.store(true, std::sync::atomic::Ordering::Release);
-            native_dialog::DialogBuilder::message()
-                .set_text("FATAL: SIGABRT\n\nProcess aborted itself.")
-                .set_title(":O")
-                .set_level(native_dialog::MessageLevel::Error)
-                .alert()
-                .show()
-                .unwrap_or_else(|_| {
-                    panic!("The dialog failed.\nFATAL: SIGABRT\n\nProcess aborted itself.")
-                });
-            exit(134);
-        })
-        .is_err()
-        {
-            error!("Failed to set SIGABRT hook.");
-        };
-    };
+    std::panic::set_hook(Box::new(crate::crash::handle_panic));
+    // handlers only record the signal. Saving and quitting happen in the main loop, since
+    // almost nothing is safe to do from inside a signal handler.
+    ctrlc::set_handler(|| request_shutdown(SIGINT))
+        .unwrap_or_else(|_| error!("Failed to set ctrl-c hook."));
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    if unsafe { signal_hook::low_level::register(SIGTERM, || request_shutdown(SIGTERM)) }.is_err() {
+        error!("Failed to set SIGTERM hook.");
+    }
 }
 
-pub fn get_mouse_angle() -> f32 {
-    ((Vec2::from(screen_size()) / 2. - Vec2::from(mouse_position())).to_angle() - PI).abs()
+fn request_shutdown(signal: i32) {
+    SHUTDOWN_SIGNAL.store(signal, std::sync::atomic::Ordering::Release);
 }
-pub fn get_mouse_angle_centered(world: &World) -> f32 {
-    ((crate::renderer::world::player_on_scr(world) - Vec2::from(mouse_position())).to_angle() - PI)
-        .abs()
+
+/// The signal that asked the game to quit, if any. Checked once per frame by the main loop.
+pub fn shutdown_signal() -> Option<i32> {
+    match SHUTDOWN_SIGNAL.load(std::sync::atomic::Ordering::Acquire) {
+        0 => None,
+        signal => Some(signal),
+    }
 }
 
-pub fn log_frame(s: String) {
-    let _ = DEBUG_TX.get().unwrap().unbounded_send(s);
+/// Wraps `angle` into `[-PI, PI)`.
+pub fn normalize_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(2. * PI) - PI
 }
 
-pub fn find_in_distance<'a>(
-    enemies: &'a mut Vec<Enemy>,
+/// Signed shortest difference `a - b`, wrapped into `[-PI, PI)`.
+pub fn angle_diff(a: f32, b: f32) -> f32 {
+    normalize_angle(a - b)
+}
+
+/// Angle of a screen/world space vector (y pointing down), counter-clockwise as seen on screen.
+pub fn screen_angle(v: Vec2) -> f32 {
+    vec2(v.x, -v.y).to_angle()
+}
+
+/// Unit vector in screen/world space pointing at `angle`. The inverse of `screen_angle`.
+pub fn angle_dir(angle: f32) -> Vec2 {
+    vec2(angle.cos(), -angle.sin())
+}
+
+pub fn find_in_distance(
+    enemies: &mut Vec<Enemy>,
     center: Vec2,
     dist: f32,
-) -> Result<Vec<&'a mut Enemy>> {
+) -> Result<Vec<&mut Enemy>> {
     let find_y_center = center.y;
     let bottom = enemies
         .par_iter()
@@ -155,8 +142,89 @@ pub fn find_in_distance<'a>(
     }
 }
 
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
-#[repr(u8)]
-pub enum PlayerAnimation {
-    Idle = 0,
+/// Time since the unix epoch. Zero if the system clock is set before it.
+pub fn unix_now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Formats seconds since the unix epoch as `YYYY-MM-DD HH:MM` in UTC.
+pub fn format_unix_time(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let (hour, min) = (secs % 86400 / 3600, secs % 3600 / 60);
+    // civil date from days since 1970-01-01, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!("{year:04}-{month:02}-{day:02} {hour:02}:{min:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 1e-4;
+
+    /// Angles sweeping several turns in both directions, in uneven steps.
+    fn angles() -> impl Iterator<Item = f32> {
+        (-2000..=2000).map(|i| i as f32 * 0.0173)
+    }
+
+    #[test]
+    fn normalize_angle_stays_in_range() {
+        for a in angles() {
+            let n = normalize_angle(a);
+            assert!((-PI..PI).contains(&n), "{a} -> {n}");
+        }
+    }
+
+    #[test]
+    fn normalize_angle_ignores_whole_turns() {
+        for a in angles() {
+            for turns in -3..=3 {
+                let shifted = a + turns as f32 * 2. * PI;
+                assert!(angle_diff(normalize_angle(shifted), normalize_angle(a)).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn angle_diff_wraps_around() {
+        assert!((angle_diff(PI - 0.1, -PI + 0.1) + 0.2).abs() < EPS);
+        assert!((angle_diff(-PI + 0.1, PI - 0.1) - 0.2).abs() < EPS);
+        assert!(angle_diff(0.3, 0.3 + 2. * PI).abs() < EPS);
+        for a in angles() {
+            for b in [-3., -1., 0., 0.5, 2.5] {
+                let d = angle_diff(a, b);
+                assert!((-PI..PI).contains(&d));
+                // b + d points the same way as a.
+                assert!(angle_dir(b + d).distance(angle_dir(a)) < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn unix_now_formats_as_a_recent_date() {
+        let now = unix_now();
+        assert!(now.as_secs() > 1_700_000_000, "{now:?}");
+        assert!(format_unix_time(now.as_secs()).as_str() > "2023-11-14 22:13");
+        assert_eq!(format_unix_time(0), "1970-01-01 00:00");
+    }
+
+    #[test]
+    fn screen_angle_inverts_angle_dir() {
+        for a in angles() {
+            assert!(angle_diff(screen_angle(angle_dir(a)), a).abs() < 1e-3);
+        }
+        // y points down on screen, so up is a quarter turn counter-clockwise.
+        assert!((screen_angle(vec2(0., -1.)) - PI / 2.).abs() < EPS);
+        assert!((screen_angle(vec2(-1., 0.)).abs() - PI).abs() < EPS);
+    }
 }